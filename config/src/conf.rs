@@ -10,9 +10,54 @@ pub struct UI {
     pub background_click: String,
 }
 
+/// Limits applied to scripts, so a broken script can not freeze the app.
+/// Not every backend can enforce every limit, each field says which ones do.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Limits {
+    /// Operations a single call into a script may run, 0 for no limit.
+    /// Rhai counts its operations, Lua its function calls and loop iterations, WebAssembly its instructions
+    pub max_operations: u64,
+    /// Rhai only, Luau has a fixed limit
+    pub max_call_depth: usize,
+    /// Rhai only
+    pub max_expr_depth: usize,
+    /// Expression depth inside functions, Rhai only
+    pub max_function_expr_depth: usize,
+    /// In bytes, Rhai only
+    pub max_string_size: usize,
+    /// Rhai only
+    pub max_array_size: usize,
+    /// Rhai only
+    pub max_map_size: usize,
+    /// Bytes of memory a script may use, 0 for no limit. Lua and WebAssembly only
+    pub max_memory: usize,
+    /// Milliseconds a single call into a script may take before it is aborted, 0 for no limit.
+    /// Every backend
+    pub frame_budget_ms: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_operations: 10_000_000,
+            max_call_depth: 64,
+            max_expr_depth: 64,
+            max_function_expr_depth: 32,
+            max_string_size: 1024 * 1024,
+            max_array_size: 100_000,
+            max_map_size: 100_000,
+            max_memory: 256 * 1024 * 1024,
+            frame_budget_ms: 500,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Config {
-    pub ui: UI,
+    pub ui: Option<UI>,
+    #[serde(default)]
+    pub limits: Limits,
 }
 
 impl Config {
@@ -29,6 +29,12 @@ pub struct Console {
     config: Config,
     input: ConsoleInput,
     input_prev: ConsoleInput,
+    /// Last printed message and how many times it was repeated
+    last: Option<(String, usize)>,
+    /// Commands handled by the application instead of cvars
+    commands: Vec<&'static str>,
+    /// Entered command waiting for [`Console::take_command`]
+    command: Option<String>,
 }
 
 impl Console {
@@ -40,13 +46,35 @@ impl Console {
             config: Config::default(),
             input: ConsoleInput::new(),
             input_prev: ConsoleInput::new(),
+            last: None,
+            commands: vec![],
+            command: None,
         }
     }
 
+    /// Print a line, identical consecutive lines are collapsed into one with a counter.
     pub fn print(&mut self, text: impl Into<String>) {
         let text = text.into();
+
+        if let Some((last, count)) = &mut self.last {
+            let is_last_line = self
+                .console
+                .history
+                .last()
+                .is_some_and(|line| !line.is_input);
+
+            if *last == text && is_last_line {
+                *count += 1;
+                if let Some(line) = self.console.history.last_mut() {
+                    line.text = format!("{text} (x{count})");
+                }
+                return;
+            }
+        }
+
         println!("{text}");
-        self.console.print(text);
+        self.console.print(text.clone());
+        self.last = Some((text, 1));
     }
 
     pub fn log(&mut self, text: impl Display) {
@@ -62,6 +90,19 @@ impl Console {
         self.print(format!("{} {text}", "[WARN]".bright_yellow()));
     }
 
+    /// Handle `name` in the application: entering a line starting with it
+    /// makes it available from [`Console::take_command`] instead of setting a cvar.
+    pub fn add_command(&mut self, name: &'static str) {
+        if !self.commands.contains(&name) {
+            self.commands.push(name);
+        }
+    }
+
+    /// The last entered command added with [`Console::add_command`], if it was not taken yet.
+    pub fn take_command(&mut self) -> Option<String> {
+        self.command.take()
+    }
+
     /// Process input, handle opening and closing, draw the console.
     ///
     /// Call this every frame after your game's rendering code so the console is drawn on top.
@@ -75,7 +116,14 @@ impl Console {
             self.process_input();
             self.draw_console();
             if !self.input_prev.enter && self.input.enter && !self.console.prompt.is_empty() {
-                self.console.enter(cvars);
+                let name = self.console.prompt.split_whitespace().next().unwrap_or_default();
+                if self.commands.contains(&name) {
+                    let command = std::mem::take(&mut self.console.prompt);
+                    self.print(format!("> {command}"));
+                    self.command = Some(command);
+                } else {
+                    self.console.enter(cvars);
+                }
             }
         }
     }
@@ -0,0 +1,80 @@
+//! An application embedding the engine, giving scripts functions, a type and a constant of its own.
+//!
+//! Run with `cargo run -p engine --example embed`.
+
+use std::cell::RefCell;
+use std::fs;
+use std::rc::Rc;
+
+use console::Console;
+use engine::ffi::ApiResult;
+use engine::{Context, Engine, ErrorMap, HostApi, HostType, ScriptDir, ScriptEngine, ScriptId};
+
+/// An account of the bank below, scripts can only pass it around.
+#[derive(Clone)]
+struct Account(usize);
+
+impl HostType for Account {
+    const NAME: &'static str = "Account";
+}
+
+const SCRIPT: &str = r#"
+fn update() {
+    let savings = open_account("savings");
+    deposit(savings, INTEREST);
+}
+"#;
+
+fn main() -> anyhow::Result<()> {
+    let root = std::env::temp_dir().join(format!("kgames-embed-{}", std::process::id()));
+    let ctx = Context::new(&root);
+    ctx.dirs().create()?;
+    let path = ctx.dirs().scripts.join("bank.rhai");
+    fs::write(&path, SCRIPT)?;
+
+    // Name and balance of every account
+    let bank: Rc<RefCell<Vec<(String, i64)>>> = Rc::default();
+
+    let mut api = HostApi::new();
+    let accounts = bank.clone();
+    let deposits = bank.clone();
+    api.host_type::<Account>()
+        .constant("INTEREST", 5_i64)
+        .function("open_account", move |name: String| -> ApiResult<Account> {
+            let mut accounts = accounts.borrow_mut();
+            let index = match accounts.iter().position(|(n, _)| *n == name) {
+                Some(index) => index,
+                None => {
+                    accounts.push((name, 0));
+                    accounts.len() - 1
+                }
+            };
+            Ok(Account(index))
+        })
+        .function("deposit", move |account: Account, amount: i64| -> ApiResult<()> {
+            if amount < 0 {
+                return Err(format!("can not deposit {amount}"));
+            }
+            deposits.borrow_mut()[account.0].1 += amount;
+            Ok(())
+        });
+
+    // The API must be exposed before loading scripts
+    let mut engine = Engine::new(ctx);
+    engine.expose_api(&api);
+
+    let mut console = Console::new();
+    let mut errors = ErrorMap::new();
+    engine.load_scripts(&mut console, &mut errors, &[ScriptDir::Scripts])?;
+
+    let id = ScriptId::new(&path);
+    for _ in 0..3 {
+        engine.call_function(&id, "update")?;
+    }
+    for (name, balance) in bank.borrow().iter() {
+        println!("{name}: {balance}");
+    }
+
+    fs::remove_dir_all(&root)?;
+    Ok(())
+}
@@ -0,0 +1,313 @@
+//! Functions available to scripts, declared once for every backend.
+//!
+//! Each backend turns the arguments of a call into [`Value`]s, picks the binding
+//! whose parameters match and turns the result back into a value of its language.
+//! Adding a function to [`BINDINGS`] makes it available in every language and in the API reference.
+//! The backends get them through a [`HostApi`](crate::host::HostApi), with what the embedder adds.
+
+use macroquad::prelude::*;
+
+pub use game_core::bindings::{ApiType, Type, Value};
+
+use crate::assets::{FontHandle, SoundHandle};
+use crate::ffi::*;
+use crate::{Context, LogLevel};
+
+/// A function scripts can call.
+/// Several bindings can share a name when their parameters differ, the first one matching is called.
+pub struct Binding {
+    pub name: &'static str,
+    pub doc: &'static str,
+    pub params: &'static [(&'static str, Type)],
+    pub returns: Option<Type>,
+    /// Called with the context of the engine and one value per parameter, of the types in [`Binding::params`]
+    pub func: fn(&Context, Vec<Value>) -> ApiResult<Value>,
+}
+
+/// `|ctx|` names the [`Context`] the functions are called with.
+macro_rules! bindings {
+    (
+        |$ctx:ident|
+        $(
+            $(#[doc = $doc:literal])*
+            fn $name:ident($($param:ident: $ty:ty),* $(,)?) $(-> $ret:ty)? $body:block
+        )*
+    ) => {
+        /// Every function available to scripts.
+        pub static BINDINGS: &[Binding] = &[
+            $(
+                Binding {
+                    name: stringify!($name),
+                    doc: concat!($($doc),*),
+                    params: &[$((stringify!($param), <$ty as ApiType>::TYPE)),*],
+                    returns: bindings!(@returns $($ret)?),
+                    func: |$ctx, args| {
+                        let _ = $ctx;
+                        #[allow(unused_mut, unused_variables)]
+                        let mut args = args.into_iter();
+                        $(
+                            let $param = args
+                                .next()
+                                .and_then(<$ty as ApiType>::from_value)
+                                .ok_or_else(|| format!(
+                                    "{}(): wrong type for `{}`",
+                                    stringify!($name),
+                                    stringify!($param),
+                                ))?;
+                        )*
+                        let result $(: $ret)? = $body;
+                        Ok(ApiType::into_value(result))
+                    },
+                },
+            )*
+        ];
+    };
+    (@returns) => { None };
+    (@returns $ret:ty) => { Some(<$ret as ApiType>::TYPE) };
+}
+
+bindings! {
+    |ctx|
+
+    // Actions
+    /// Fill the screen with a color
+    fn clear(color: Color) {
+        clear_background(color)
+    }
+    fn text(text: String, x: f32, y: f32, size: f32, color: Color) {
+        draw_text(&text, x, y, size, color);
+    }
+    /// Draw text with a font from `load_font`
+    fn text(text: String, x: f32, y: f32, size: f32, color: Color, font: FontHandle) {
+        let params = TextParams {
+            font: Some(font.get()),
+            font_size: size as u16,
+            color,
+            ..Default::default()
+        };
+        draw_text_ex(&text, x, y, params);
+    }
+    fn circle(x: f32, y: f32, r: f32, color: Color) {
+        draw_circle(x, y, r, color)
+    }
+    fn line(x1: f32, y1: f32, x2: f32, y2: f32, thickness: f32, color: Color) {
+        draw_line(x1, y1, x2, y2, thickness, color)
+    }
+    fn triangle(v1: Vec2, v2: Vec2, v3: Vec2, color: Color) {
+        draw_triangle(v1, v2, v3, color)
+    }
+    fn rectangle(x: f32, y: f32, w: f32, h: f32, color: Color) {
+        draw_rectangle(x, y, w, h, color)
+    }
+    fn rectangle_lines(x: f32, y: f32, w: f32, h: f32, thickness: f32, color: Color) {
+        draw_rectangle_lines(x, y, w, h, thickness, color)
+    }
+    /// Show a message in a dialog, blocks until it is closed
+    fn msgbox(title: String, message: String) {
+        let _ = msgbox::create(&title, &message, msgbox::IconType::Info);
+    }
+    fn overlaps(a: Rect, b: Rect) -> bool {
+        a.overlaps(&b)
+    }
+
+    // Textures
+    fn texture(texture: Texture2D, x: f32, y: f32, tint: Color) {
+        draw_texture(&texture, x, y, tint)
+    }
+    /// Draw a builtin texture by name
+    fn texture(name: String, x: f32, y: f32, tint: Color) {
+        draw_texture_stored(ctx, &name, x, y, tint)?
+    }
+    fn texture_ex(texture: Texture2D, x: f32, y: f32, tint: Color, dest_size: Vec2, rotation: f32) {
+        let params = DrawTextureParams {
+            dest_size: Some(dest_size),
+            rotation,
+            ..Default::default()
+        };
+        draw_texture_ex(&texture, x, y, tint, params)
+    }
+    fn texture_pro(
+        texture: Texture2D,
+        x: f32,
+        y: f32,
+        tint: Color,
+        dest_size: Vec2,
+        source: Rect,
+        rotation: f32,
+        pivot: Vec2,
+    ) {
+        let params = DrawTextureParams {
+            dest_size: Some(dest_size),
+            source: Some(source),
+            rotation,
+            pivot: Some(pivot),
+            ..Default::default()
+        };
+        draw_texture_ex(&texture, x, y, tint, params)
+    }
+    /// Load an image from the game folder or `assets/`
+    fn load_texture(path: String) -> Texture2D {
+        load_texture_sync(ctx, &path)?
+    }
+    /// One of the builtin textures, by name
+    fn get_texture(name: String) -> Texture2D {
+        load_texture_stored(ctx, &name)?
+    }
+
+    // Sounds and fonts
+    /// Load a `.wav` or `.ogg` sound from the game folder or `assets/`
+    fn load_sound(path: String) -> SoundHandle {
+        load_sound_sync(ctx, &path)?
+    }
+    fn play_sound(sound: SoundHandle) {
+        macroquad::audio::play_sound_once(&sound.get())
+    }
+    fn play_sound(sound: SoundHandle, looped: bool, volume: f32) {
+        let params = macroquad::audio::PlaySoundParams { looped, volume };
+        macroquad::audio::play_sound(&sound.get(), params)
+    }
+    fn stop_sound(sound: SoundHandle) {
+        macroquad::audio::stop_sound(&sound.get())
+    }
+    /// Load a `.ttf` font from the game folder or `assets/`
+    fn load_font(path: String) -> FontHandle {
+        load_font_sync(ctx, &path)?
+    }
+
+    // Preloading
+    /// Load an asset behind the loading screen, or in the background while playing
+    fn preload(path: String) {
+        preload(ctx, &[path.as_str()])?
+    }
+    fn preload(paths: Vec<String>) {
+        preload(ctx, &paths.iter().map(String::as_str).collect::<Vec<_>>())?
+    }
+    /// From 0.0 to 1.0, 1.0 when nothing is being loaded
+    fn loading_progress() -> f32 {
+        loading_progress(ctx)
+    }
+
+    // Information
+    /// Seconds since the last frame
+    fn deltatime() -> f32 {
+        get_frame_time()
+    }
+    fn screen_width() -> f32 {
+        screen_width()
+    }
+    fn screen_height() -> f32 {
+        screen_height()
+    }
+    fn last_keypress() -> Option<KeyCode> {
+        get_last_key_pressed()
+    }
+    fn key_down(key: KeyCode) -> bool {
+        is_key_down(key)
+    }
+    fn key_pressed(key: KeyCode) -> bool {
+        is_key_pressed(key)
+    }
+    fn key_released(key: KeyCode) -> bool {
+        is_key_released(key)
+    }
+    fn mouse_down(button: MouseButton) -> bool {
+        is_mouse_button_down(button)
+    }
+    fn mouse_pressed(button: MouseButton) -> bool {
+        is_mouse_button_pressed(button)
+    }
+    fn mouse_released(button: MouseButton) -> bool {
+        is_mouse_button_released(button)
+    }
+    fn fps() -> i32 {
+        get_fps()
+    }
+
+    // Logging
+    /// Print a line in the console, prefixed with the name of the script.
+    /// The lines of the last session are shown in the games browser
+    fn log(text: String) {
+        ctx.script_log(LogLevel::Info, text)
+    }
+    fn warn(text: String) {
+        ctx.script_log(LogLevel::Warn, text)
+    }
+    fn error(text: String) {
+        ctx.script_log(LogLevel::Error, text)
+    }
+
+    // Constructors
+    fn vec2(x: f32, y: f32) -> Vec2 {
+        vec2(x, y)
+    }
+    fn vec3(x: f32, y: f32, z: f32) -> Vec3 {
+        vec3(x, y, z)
+    }
+    fn rect(x: f32, y: f32, w: f32, h: f32) -> Rect {
+        Rect::new(x, y, w, h)
+    }
+    fn color(r: f32, g: f32, b: f32, a: f32) -> Color {
+        Color::new(r, g, b, a)
+    }
+}
+
+/// A type scripts can use, with the properties every backend exposes.
+pub struct TypeBinding {
+    pub name: &'static str,
+    /// Name, type and whether it can be assigned
+    pub properties: &'static [(&'static str, Type, bool)],
+}
+
+/// Every type available to scripts.
+pub static TYPES: &[TypeBinding] = &[
+    TypeBinding {
+        name: "Vec2",
+        properties: &[("x", Type::Float, true), ("y", Type::Float, true)],
+    },
+    TypeBinding {
+        name: "Vec3",
+        properties: &[
+            ("x", Type::Float, true),
+            ("y", Type::Float, true),
+            ("z", Type::Float, true),
+        ],
+    },
+    TypeBinding {
+        name: "Rect",
+        properties: &[
+            ("x", Type::Float, true),
+            ("y", Type::Float, true),
+            ("w", Type::Float, true),
+            ("h", Type::Float, true),
+        ],
+    },
+    TypeBinding {
+        name: "Color",
+        properties: &[
+            ("r", Type::Float, true),
+            ("g", Type::Float, true),
+            ("b", Type::Float, true),
+            ("a", Type::Float, true),
+        ],
+    },
+    TypeBinding {
+        name: "Texture",
+        properties: &[("width", Type::Float, false), ("height", Type::Float, false)],
+    },
+    TypeBinding {
+        name: "Sound",
+        properties: &[],
+    },
+    TypeBinding {
+        name: "Font",
+        properties: &[],
+    },
+    TypeBinding {
+        name: "Key",
+        properties: &[],
+    },
+    TypeBinding {
+        name: "Mouse",
+        properties: &[],
+    },
+];
@@ -0,0 +1,153 @@
+//! Headless script checking, used by `kgames check`.
+//!
+//! Scripts are only compiled, never run, so no window is required.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use super::*;
+
+/// Functions every game script has to define.
+pub const REQUIRED_HOOKS: [&str; 2] = ["update", "draw"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A single problem found in a script.
+///
+/// `line` and `column` start at 1, and are 0 if the position is unknown.
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub path: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn error(path: impl Into<PathBuf>, message: impl ToString) -> Self {
+        Self {
+            path: path.into(),
+            line: 0,
+            column: 0,
+            severity: Severity::Error,
+            message: message.to_string(),
+        }
+    }
+    pub fn warning(path: impl Into<PathBuf>, message: impl ToString) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::error(path, message)
+        }
+    }
+    pub fn at(mut self, line: usize, column: usize) -> Self {
+        self.line = line;
+        self.column = column;
+        self
+    }
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+/// An error of the engine, prefixed with its kind and at its position if it has one.
+impl From<&EngineError> for Diagnostic {
+    fn from(error: &EngineError) -> Self {
+        let path = error.path().unwrap_or(Path::new(""));
+        let diagnostic = Self::error(path, format!("{}: {}", error.kind(), error.message()));
+        match error.script_error() {
+            Some(e) => diagnostic.at(e.line, e.column),
+            None => diagnostic,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+
+        write!(f, "{}", self.path.display())?;
+        if self.line > 0 {
+            write!(f, ":{}:{}", self.line, self.column)?;
+        }
+        write!(f, ": {severity}: {}", self.message)
+    }
+}
+
+/// The result of checking a single script.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptReport {
+    pub path: PathBuf,
+    pub example: bool,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl ScriptReport {
+    pub fn is_ok(&self) -> bool {
+        !self.diagnostics.iter().any(Diagnostic::is_error)
+    }
+}
+
+/// Compile every script in `from`, without running them.
+///
+/// Errors that do not belong to a single script (such as unreadable directories)
+/// are returned as a report of their own.
+pub fn check_scripts<E: ScriptEngine>(engine: &E, from: &[ScriptDir]) -> Vec<ScriptReport> {
+    let mut errors = vec![];
+    let found = find_scripts(engine.context().dirs(), from, E::extensions(), &mut errors);
+
+    let mut reports: Vec<_> = errors
+        .into_iter()
+        .map(|(path, e)| ScriptReport {
+            diagnostics: vec![Diagnostic {
+                path: path.clone(),
+                ..Diagnostic::from(&e)
+            }],
+            path,
+            example: false,
+        })
+        .collect();
+
+    for FoundScript { path, example, .. } in found {
+        let diagnostics = match fs::read(&path) {
+            Ok(source) => engine.check_script(&path, &source),
+            Err(e) => vec![Diagnostic::from(&EngineError::io(&path, e))],
+        };
+
+        reports.push(ScriptReport {
+            path,
+            example,
+            diagnostics,
+        });
+    }
+
+    reports
+}
+
+/// Serialize reports to JSON for use by other tools.
+pub fn to_json(reports: &[ScriptReport]) -> String {
+    serde_json::to_string_pretty(reports).expect("reports are always serializable")
+}
+
+/// Report hooks from [`REQUIRED_HOOKS`] not present in `defined`.
+pub fn missing_hooks<'a>(
+    path: &Path,
+    defined: impl Iterator<Item = &'a str> + Clone,
+) -> Vec<Diagnostic> {
+    REQUIRED_HOOKS
+        .iter()
+        .filter(|hook| !defined.clone().any(|name| name == **hook))
+        .map(|hook| Diagnostic::error(path, format!("missing function `{hook}()`")))
+        .collect()
+}
@@ -1,40 +1,38 @@
-use console::Console;
-
 use super::*;
-use std::cell::LazyCell;
+use std::cell::Cell;
+use include_dir::{include_dir, Dir};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
 use std::ffi::OsStr;
 use std::fs;
-use std::fs::DirEntry;
+use std::hash::{Hash, Hasher};
 use std::io;
-use std::path::Path;
+use std::path::{Component, Path};
 use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
 pub const GLOBAL_DIR: &str = env!("CARGO_PKG_NAME");
 
-// Types
-#[cfg(feature = "rhai-engine")]
-pub mod scripting {
-    pub use rhai::{EvalAltResult, ImmutableString};
-    pub type Error = Box<EvalAltResult>;
-    pub type Result<T> = std::result::Result<T, Error>;
-}
-
-#[cfg(feature = "lua-engine")]
-mod scripting {
-    pub type Error = mlua::Error;
-    pub type Result<T> = std::result::Result<T, Error>;
-}
-
+#[derive(Clone)]
 pub struct Dirs {
     pub root: PathBuf,
     pub scripts: PathBuf,
     pub examples: PathBuf,
     pub assets: PathBuf,
+    pub lib: PathBuf,
+    /// Native plugins, see [`crate::plugins`]
+    pub plugins: PathBuf,
 }
 impl Dirs {
     pub fn create(&self) -> io::Result<()> {
-        let mut dirs = dirs();
-        let dirs = [&dirs.root, &dirs.scripts, &dirs.examples, &dirs.assets];
+        let dirs = [
+            &self.root,
+            &self.scripts,
+            &self.examples,
+            &self.assets,
+            &self.lib,
+            &self.plugins,
+        ];
 
         for dir in dirs {
             if !dir.is_dir() {
@@ -50,88 +48,343 @@ impl Dirs {
     }
 }
 
-pub static mut DIRS: LazyCell<Dirs> = LazyCell::new(|| {
-    let root = PathBuf::from(GLOBAL_DIR);
-    Dirs {
-        scripts: root.join("scripts"),
-        assets: root.join("assets"),
-        examples: root.join("examples"),
-        root,
+impl Dirs {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        let root = root.into();
+        Self {
+            scripts: root.join("scripts"),
+            assets: root.join("assets"),
+            examples: root.join("examples"),
+            lib: root.join("lib"),
+            plugins: root.join("plugins"),
+            root,
+        }
     }
-});
+}
 
-pub fn dirs() -> &'static Dirs {
-    // very not good trick
-    #[allow(static_mut_refs)]
-    unsafe {
-        &DIRS
+/// Whether `path` is relative and can not escape the folder it is joined to.
+pub fn is_contained(path: &Path) -> bool {
+    !path.as_os_str().is_empty()
+        && path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+/// Identifies the contents of a script, to recognize it after it was renamed.
+pub(crate) fn source_hash(contents: &(impl Hash + ?Sized)) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Aborts calls into scripts taking longer than the budget or running too many operations, such as infinite loops.
+#[derive(Default)]
+pub(crate) struct Watchdog {
+    /// `Duration::ZERO` disables the watchdog
+    budget: Cell<Duration>,
+    started: Cell<Option<Instant>>,
+    /// Operations a call may run, for backends without their own count. 0 for no limit
+    max_operations: Cell<u64>,
+    operations: Cell<u64>,
+}
+
+impl Watchdog {
+    pub fn set_budget(&self, budget: Duration) {
+        self.budget.set(budget);
+    }
+    pub fn set_max_operations(&self, max: u64) {
+        self.max_operations.set(max);
+    }
+    /// Call before running any script code.
+    pub fn start(&self) {
+        self.started.set(Some(Instant::now()));
+        self.operations.set(0);
+    }
+    /// Count an operation of the running call, `false` once it ran all it may.
+    pub fn count_operation(&self) -> bool {
+        let operations = self.operations.get() + 1;
+        self.operations.set(operations);
+        let max = self.max_operations.get();
+        max == 0 || operations <= max
+    }
+    pub fn operations_message(&self) -> String {
+        format!(
+            "Aborted after {} operations, the script may be stuck in an infinite loop",
+            self.max_operations.get()
+        )
+    }
+    pub fn expired(&self) -> bool {
+        let budget = self.budget.get();
+        !budget.is_zero() && self.started.get().is_some_and(|t| t.elapsed() > budget)
+    }
+    pub fn abort_message(&self) -> String {
+        format!(
+            "Aborted after {:?}, the script may be stuck in an infinite loop",
+            self.budget.get()
+        )
     }
 }
 
-pub fn create_readme(filename: impl AsRef<Path>) -> io::Result<PathBuf> {
+/// Write the bundled examples written in the language with `extension`, keeping existing files.
+pub fn write_examples(
+    dirs: &Dirs,
+    extension: &str,
+    warnings: &mut Vec<String>,
+) -> Result<(), Vec<io::Error>> {
+    static EXAMPLES: Dir = include_dir!("$CARGO_MANIFEST_DIR/../res/examples");
+
+    let mut errors = vec![];
+
+    for example in EXAMPLES.files() {
+        if example.path().extension() != Some(OsStr::new(extension)) {
+            continue;
+        }
+        let write_path = dirs.examples.join(example.path());
+
+        if write_path.is_file() {
+            warnings.push(format!("File {write_path:?} already exists. To overwrite the example, rename or delete it."));
+            continue;
+        }
+
+        if let Err(e) = fs::write(write_path, example.contents()) {
+            errors.push(e);
+        }
+    }
+
+    if errors.len() > 0 {
+        Err(errors)
+    } else {
+        Ok(())
+    }
+}
+
+pub fn create_readme(dirs: &Dirs, filename: impl AsRef<Path>) -> io::Result<PathBuf> {
     static README: &str = include_str!("../../README.md");
-    let path = dirs().root.join(filename);
+    let path = dirs.root.join(filename);
     fs::write(&path, README)?;
     Ok(path)
 }
 
-pub fn read_files_from_dir<P, E>(
-    from: &[P],
-    ext: E,
-    console: &mut Console,
+/// File name (without extension) of the script of a folder-per-game.
+pub const MAIN_SCRIPT: &str = "main";
+/// How deep to look for scripts in subfolders
+const MAX_DEPTH: usize = 8;
+
+/// A script found by [`find_scripts`].
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FoundScript {
+    pub path: PathBuf,
+    /// Subfolders between the script directory and the script, separated by `/`.
+    /// Empty for scripts at the top level.
+    pub category: String,
+    /// Folder of a folder-per-game, containing its main script and assets
+    pub game_dir: Option<PathBuf>,
+    pub example: bool,
+}
+
+/// Find all files ending in one of `exts` in the given directories and their subfolders, without reading them.
+/// A subfolder containing a `main` script is a single game, its other files are not scripts of their own.
+pub fn find_scripts(
+    dirs: &Dirs,
+    from: &[ScriptDir],
+    exts: &[&str],
     errors: &mut ErrorMap,
-) -> anyhow::Result<Vec<(DirEntry, Vec<u8>, bool)>>
-where
-    P: AsRef<Path>,
-    E: AsRef<OsStr>,
-{
-    let mut contents = vec![];
-    let mut err = None;
+) -> Vec<FoundScript> {
+    let mut found = vec![];
 
     for src in from {
-        let example = src.as_ref() == ScriptDir::Examples.path();
-
-        console.log(format!("==> Loading scripts from {:?}", src.as_ref()));
-
-        match fs::read_dir(src) {
-            Ok(dir) => {
-                for entry in dir {
-                    match entry {
-                        Ok(entry) => {
-                            let path = entry.path();
-                            let extension = path.extension();
-
-                            // If extension matches, read the file.
-                            if extension.is_some_and(|e| e == ext.as_ref()) {
-                                match fs::read(&path) {
-                                    Err(e) => {
-                                        console.err(format!("Failed to read {path:?}: {e}"));
-                                        errors.push((path, e.into()));
-                                    }
-                                    Ok(c) => {
-                                        contents.push((entry, c, example));
-                                    }
-                                }
-                            } else {
-                                console
-                                    .warn(format!("Skipping file with wrong extension: {path:?}"));
-                            }
-                        }
-                        Err(e) => {
-                            console.err(format!("Skipping file: {e}"));
-                        }
-                    }
-                }
-            }
+        let example = *src == ScriptDir::Examples;
+        walk_scripts(src.path(dirs), exts, example, &mut found, errors);
+    }
+
+    found.sort();
+    found
+}
+
+fn walk_scripts(
+    root: &Path,
+    exts: &[&str],
+    example: bool,
+    found: &mut Vec<FoundScript>,
+    errors: &mut ErrorMap,
+) {
+    let category = |dir: &Path| {
+        let relative = dir.strip_prefix(root).unwrap_or(dir);
+        let parts: Vec<_> = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect();
+        parts.join("/")
+    };
+
+    walk_dirs(root, &mut |dir, depth, entries| {
+        let entries = match entries {
+            Ok(entries) => entries,
             Err(e) => {
-                errors.push((src.as_ref().to_path_buf(), e.into()));
+                errors.push((dir.to_path_buf(), EngineError::io(dir, e)));
+                return false;
             }
+        };
+
+        // Folder-per-game
+        let main = exts
+            .iter()
+            .map(|ext| dir.join(MAIN_SCRIPT).with_extension(ext))
+            .find(|main| main.is_file());
+        if let (true, Some(main)) = (depth > 0, main) {
+            found.push(FoundScript {
+                path: main,
+                category: category(dir.parent().unwrap_or(root)),
+                game_dir: Some(dir.to_path_buf()),
+                example,
+            });
+            return false;
+        }
+
+        for entry in entries {
+            let path = entry.path();
+            if !path.is_dir() && path.extension().is_some_and(|e| exts.iter().any(|ext| e == *ext)) {
+                found.push(FoundScript {
+                    path,
+                    category: category(dir),
+                    game_dir: None,
+                    example,
+                });
+            }
+        }
+        true
+    });
+}
+
+/// Called by [`walk_dirs`] with a folder, its depth and its entries.
+pub(crate) type VisitDir<'a> = dyn FnMut(&Path, usize, io::Result<&[fs::DirEntry]>) -> bool + 'a;
+
+/// Walk `root` and its subfolders the way scripts are searched: at most [`MAX_DEPTH`] deep,
+/// skipping hidden folders and folders already walked through a symlink.
+/// `visit` returns whether to walk the subfolders of a folder.
+pub(crate) fn walk_dirs(root: &Path, visit: &mut VisitDir) {
+    walk_dir(root, 0, &mut HashSet::new(), visit);
+}
+
+fn walk_dir(
+    dir: &Path,
+    depth: usize,
+    visited: &mut HashSet<PathBuf>,
+    visit: &mut VisitDir,
+) {
+    // A symlink to a parent folder would be walked forever
+    if let Ok(canonical) = dir.canonicalize() {
+        if !visited.insert(canonical) {
+            return;
         }
     }
 
-    if let Some(err) = err {
-        Err(err)
-    } else {
-        Ok(contents)
+    let entries: Vec<_> = match fs::read_dir(dir) {
+        Ok(entries) => entries.flatten().collect(),
+        Err(e) => {
+            visit(dir, depth, Err(e));
+            return;
+        }
+    };
+    if !visit(dir, depth, Ok(&entries)) || depth >= MAX_DEPTH {
+        return;
+    }
+
+    for entry in entries {
+        let path = entry.path();
+        let hidden = entry.file_name().to_string_lossy().starts_with('.');
+        if !hidden && path.is_dir() {
+            walk_dir(&path, depth + 1, visited, visit);
+        }
+    }
+}
+
+/// An empty folder for a test, removed with its contents when dropped.
+#[cfg(test)]
+pub(crate) struct TempDir(pub PathBuf);
+#[cfg(test)]
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("kgames-test-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+}
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contained_paths() {
+        assert!(is_contained(Path::new("a")));
+        assert!(is_contained(Path::new("a/b.png")));
+        assert!(is_contained(Path::new("./a")));
+    }
+
+    #[test]
+    fn escaping_paths() {
+        assert!(!is_contained(Path::new("")));
+        assert!(!is_contained(Path::new("..")));
+        assert!(!is_contained(Path::new("../a")));
+        assert!(!is_contained(Path::new("a/../b")));
+        assert!(!is_contained(Path::new("a/..")));
+        assert!(!is_contained(Path::new("/etc/passwd")));
+    }
+
+    /// The check is lexical, its callers canonicalize the path to catch symlinks
+    #[cfg(unix)]
+    #[test]
+    fn callers_reject_symlinks_out() {
+        let tmp = TempDir::new("contained");
+        let ctx = crate::Context::new(tmp.0.join("data"));
+        let dirs = ctx.dirs();
+        dirs.create().unwrap();
+        fs::write(tmp.0.join("secret.png"), b"").unwrap();
+        fs::write(tmp.0.join("secret.rhai"), "").unwrap();
+        std::os::unix::fs::symlink(&tmp.0, dirs.assets.join("up")).unwrap();
+        std::os::unix::fs::symlink(&tmp.0, dirs.lib.join("up")).unwrap();
+
+        assert!(is_contained(Path::new("up/secret.png")));
+        let err = ctx.asset_path("up/secret.png").unwrap_err();
+        assert!(err.contains("outside of the assets folder"), "{err}");
+
+        #[cfg(feature = "rhai-engine")]
+        {
+            use rhai::ModuleResolver;
+
+            let modules = crate::rhai_modules::ModuleCache::new(dirs.root.clone());
+            let engine = rhai::Engine::new();
+            assert!(is_contained(Path::new("lib/up/secret")));
+            let err = modules
+                .resolve(&engine, None, "lib/up/secret", rhai::Position::NONE)
+                .unwrap_err();
+            assert!(matches!(*err, rhai::EvalAltResult::ErrorRuntime(..)), "{err}");
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn find_scripts_in_symlink_loop() {
+        let tmp = TempDir::new("find-loop");
+        let dirs = Dirs::new(&tmp.0);
+        dirs.create().unwrap();
+        fs::create_dir_all(dirs.scripts.join("arcade/.cache")).unwrap();
+        fs::write(dirs.scripts.join("arcade/pong.rhai"), "").unwrap();
+        fs::write(dirs.scripts.join("arcade/.cache/old.rhai"), "").unwrap();
+        std::os::unix::fs::symlink(&dirs.scripts, dirs.scripts.join("arcade/loop")).unwrap();
+
+        let mut errors = vec![];
+        let found = find_scripts(&dirs, &[ScriptDir::Scripts], &["rhai"], &mut errors);
+        assert!(errors.is_empty());
+        let paths: Vec<_> = found.iter().map(|s| (s.path.clone(), s.category.as_str())).collect();
+        assert_eq!(paths, [(dirs.scripts.join("arcade/pong.rhai"), "arcade")]);
     }
 }
@@ -0,0 +1,124 @@
+//! Scripts checking that every backend behaves the same, run by `cargo test` for each enabled backend.
+//!
+//! Each case exists once per language in `res/conformance`, with the same file stem and the same checks.
+//! A case passes when its `test()` function returns without error, cases named `fail_*`
+//! pass when they fail with the message given on their first line, e.g. `// expect: boom`.
+
+use std::collections::BTreeSet;
+use std::ffi::OsStr;
+use std::fs;
+
+use include_dir::{include_dir, Dir};
+
+use super::*;
+use crate::common::TempDir;
+
+static CASES: Dir = include_dir!("$CARGO_MANIFEST_DIR/../res/conformance");
+
+/// Function called in every case
+const TEST_FUNCTION: &str = "test";
+
+struct CaseResult {
+    name: String,
+    /// Why the case failed
+    error: Option<String>,
+}
+
+/// Write the cases for `E` into the scripts folder of its context, load them and run them.
+/// Expects an empty data folder, scripts already there would be loaded too.
+fn run<E: ScriptEngine>(engine: &mut E, console: &mut Console) -> anyhow::Result<Vec<CaseResult>> {
+    let dirs = engine.context().dirs().clone();
+    dirs.create()?;
+
+    let mut cases = vec![];
+    for case in CASES.files() {
+        let ext = case.path().extension().and_then(OsStr::to_str);
+        if !ext.is_some_and(|ext| E::extensions().contains(&ext)) {
+            continue;
+        }
+        let path = dirs.scripts.join(case.path());
+        fs::write(&path, case.contents())?;
+
+        let source = case.contents_utf8().unwrap_or_default();
+        let expected = source
+            .lines()
+            .next()
+            .and_then(|line| line.split_once("expect:"))
+            .map(|(_, message)| message.trim().to_owned());
+        cases.push((path, expected));
+    }
+    cases.sort();
+
+    // Cases failing to load are reported below, the others still run
+    let mut errors = vec![];
+    let _ = engine.load_scripts(console, &mut errors, &[ScriptDir::Scripts]);
+
+    let mut results = vec![];
+    for (path, expected) in cases {
+        let name = path
+            .file_name()
+            .map_or_else(String::new, |n| n.to_string_lossy().into_owned());
+
+        let id = ScriptId::new(&path);
+        let failure = match errors.iter().find(|(p, _)| ScriptId::new(p) == id) {
+            Some((_, e)) => Some(format!("{e:#}")),
+            None => engine
+                .call_function(&id, TEST_FUNCTION)
+                .err()
+                .map(|e| format!("{e:#}")),
+        };
+
+        let error = match (expected, failure) {
+            (None, failure) => failure,
+            (Some(expected), Some(failure)) if failure.contains(&expected) => None,
+            (Some(expected), Some(failure)) => {
+                Some(format!("expected an error containing {expected:?}, got: {failure}"))
+            }
+            (Some(expected), None) => Some(format!("expected an error containing {expected:?}")),
+        };
+
+        results.push(CaseResult { name, error });
+    }
+
+    Ok(results)
+}
+
+/// Run the cases of `E` in a new data folder and fail with every case that did not pass.
+fn check_backend<E: ScriptEngine>(name: &str, new: fn(Context) -> E) {
+    let tmp = TempDir::new(name);
+    let mut engine = new(Context::new(&tmp.0));
+    engine.expose_layer();
+
+    let results = run(&mut engine, &mut Console::new()).unwrap();
+    let failed: Vec<_> = results
+        .iter()
+        .filter_map(|r| Some(format!("{}: {}", r.name, r.error.as_ref()?)))
+        .collect();
+    assert!(!results.is_empty(), "no conformance cases for {name}");
+    assert!(failed.is_empty(), "{name} failed {failed:#?}");
+}
+
+#[cfg(feature = "rhai-engine")]
+#[test]
+fn rhai() {
+    check_backend("conformance-rhai", crate::rhai_engine::Engine::new);
+}
+
+#[cfg(feature = "lua-engine")]
+#[test]
+fn lua() {
+    check_backend("conformance-lua", crate::lua_engine::Engine::new);
+}
+
+/// The cases test the same behaviour in every language
+#[test]
+fn same_cases_in_every_language() {
+    let stems = |language: &str| {
+        CASES
+            .files()
+            .filter(|case| case.path().extension() == Some(OsStr::new(language)))
+            .filter_map(|case| case.path().file_stem().map(|stem| stem.to_owned()))
+            .collect::<BTreeSet<_>>()
+    };
+    assert_eq!(stems("rhai"), stems("lua"));
+}
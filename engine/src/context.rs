@@ -0,0 +1,306 @@
+//! State of one engine: its folders, the assets its scripts loaded, messages for the console
+//! and what each script logged.
+//!
+//! Every [`ScriptEngine`](crate::ScriptEngine) is created with a [`Context`], and functions called by scripts
+//! get it from their backend. Two engines with their own contexts share nothing.
+
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{self, Display};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use console::Console;
+use macroquad::prelude::Texture2D;
+
+use game_core::texture::AssetStore;
+
+use crate::assets::{AssetResolver, Assets};
+use crate::{is_contained, Dirs, GameScript, ScriptId, GLOBAL_DIR};
+
+/// Lines kept in the log of each script, the oldest are dropped first
+const MAX_LOG_LINES: usize = 500;
+
+/// The script whose code is being run.
+struct Running {
+    id: ScriptId,
+    /// Prefix of the lines it logs
+    name: String,
+    /// Folder of a folder-per-game, see [`Context::asset_path`]
+    game_dir: Option<PathBuf>,
+}
+
+/// A line for the console, see [`Context::flush_console`].
+enum Message {
+    Log(String),
+    Note(String),
+    Warn(String),
+    Err(String),
+}
+
+/// Severity of a line logged by a script.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogLevel {
+    /// From `debug` in rhai
+    Debug,
+    /// From `log`, and `print` in rhai
+    Info,
+    Warn,
+    Error,
+}
+
+impl Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self {
+            Self::Debug => "debug",
+            Self::Info => "info",
+            Self::Warn => "warn",
+            Self::Error => "error",
+        };
+        write!(f, "{level}")
+    }
+}
+
+/// A line logged by a script, see [`Context::script_log`].
+#[derive(Debug, Clone)]
+pub struct LogLine {
+    pub level: LogLevel,
+    pub text: String,
+}
+
+struct Inner {
+    dirs: Dirs,
+    textures: AssetStore,
+    assets: RefCell<Assets>,
+    running: RefCell<Option<Running>>,
+    messages: RefCell<Vec<Message>>,
+    logs: RefCell<HashMap<ScriptId, VecDeque<LogLine>>>,
+}
+
+/// Handle to the state of an engine, clones share it.
+#[derive(Clone)]
+pub struct Context(Rc<Inner>);
+
+impl Default for Context {
+    /// A context using the `kgames` folder.
+    fn default() -> Self {
+        Self::new(GLOBAL_DIR)
+    }
+}
+
+impl Context {
+    /// A context whose folders are in `root`, they are only created by [`Dirs::create`].
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self(Rc::new(Inner {
+            dirs: Dirs::new(root),
+            textures: AssetStore::default(),
+            assets: RefCell::default(),
+            running: RefCell::default(),
+            messages: RefCell::default(),
+            logs: RefCell::default(),
+        }))
+    }
+
+    pub fn dirs(&self) -> &Dirs {
+        &self.0.dirs
+    }
+
+    /// The assets loaded by scripts.
+    /// Panics if they are borrowed mutably, so do not keep the borrow while calling into a script.
+    pub fn assets(&self) -> Ref<'_, Assets> {
+        self.0.assets.borrow()
+    }
+    pub fn assets_mut(&self) -> RefMut<'_, Assets> {
+        self.0.assets.borrow_mut()
+    }
+
+    /// A texture bundled with kgames, loaded on first use.
+    pub fn builtin_texture(&self, name: &str) -> Option<Texture2D> {
+        self.0.textures.get_texture(name).cloned()
+    }
+
+    /// Set the script being run, assets it loads belong to it.
+    /// Script engines call this before running any script code.
+    pub fn set_running_script(&self, script: &(impl GameScript + ?Sized)) {
+        let mut current = self.0.running.borrow_mut();
+        if current.as_ref().is_some_and(|r| &r.id == script.id()) {
+            return;
+        }
+        let name = script.name().map_or_else(
+            || script.path().file_stem().unwrap_or_default().to_string_lossy(),
+            Into::into,
+        );
+        *current = Some(Running {
+            id: script.id().clone(),
+            name: name.into_owned(),
+            game_dir: script.game_dir().map(Path::to_path_buf),
+        });
+    }
+
+    /// The script set by [`Context::set_running_script`].
+    pub fn running_script(&self) -> Option<ScriptId> {
+        self.0.running.borrow().as_ref().map(|r| r.id.clone())
+    }
+
+    /// Resolve the path of an asset: relative to the folder of the running game if it exists there,
+    /// in the shared assets folder otherwise.
+    ///
+    /// Returns the canonical path, which never leaves the folder it was found in.
+    pub fn asset_path(&self, path: impl AsRef<Path>) -> Result<PathBuf, String> {
+        let path = path.as_ref();
+        if !is_contained(path) {
+            return Err(format!(
+                "Invalid asset path {path:?}, only relative paths without '..' are allowed"
+            ));
+        }
+
+        let game_dir = self.0.running.borrow().as_ref().and_then(|r| r.game_dir.clone());
+        for base in game_dir.iter().chain([&self.dirs().assets]) {
+            let Ok(base) = base.canonicalize() else {
+                continue;
+            };
+            let Ok(candidate) = base.join(path).canonicalize() else {
+                continue;
+            };
+            // NOTE: symlinks can point anywhere
+            if !candidate.starts_with(&base) {
+                return Err(format!("Asset {path:?} is outside of the assets folder"));
+            }
+            return Ok(candidate);
+        }
+
+        Err(format!("Asset {path:?} not found"))
+    }
+
+    /// Queue a line for the console, for code that has no access to it.
+    pub fn log(&self, text: impl Display) {
+        self.0.messages.borrow_mut().push(Message::Log(text.to_string()));
+    }
+    pub fn warn(&self, text: impl Display) {
+        self.0.messages.borrow_mut().push(Message::Warn(text.to_string()));
+    }
+    pub fn err(&self, text: impl Display) {
+        self.0.messages.borrow_mut().push(Message::Err(text.to_string()));
+    }
+
+    /// Log a line for the running script: it is kept in the log of the script
+    /// and queued for the console, prefixed with the name of the script.
+    pub fn script_log(&self, level: LogLevel, text: impl Display) {
+        let text = text.to_string();
+        let running = self.0.running.borrow();
+        let Some(Running { id, name, .. }) = running.as_ref() else {
+            self.queue(level, format!("[script] {text}"));
+            return;
+        };
+        self.queue(level, format!("[{name}] {text}"));
+
+        let mut logs = self.0.logs.borrow_mut();
+        let log = logs.entry(id.clone()).or_default();
+        if log.len() == MAX_LOG_LINES {
+            log.pop_front();
+        }
+        log.push_back(LogLine { level, text });
+    }
+
+    fn queue(&self, level: LogLevel, text: String) {
+        let message = match level {
+            LogLevel::Debug => Message::Note(text),
+            LogLevel::Info => Message::Log(text),
+            LogLevel::Warn => Message::Warn(text),
+            LogLevel::Error => Message::Err(text),
+        };
+        self.0.messages.borrow_mut().push(message);
+    }
+
+    /// The last lines logged by a script, oldest first.
+    pub fn script_log_lines(&self, id: &ScriptId) -> Vec<LogLine> {
+        self.0
+            .logs
+            .borrow()
+            .get(id)
+            .map_or_else(Vec::new, |log| log.iter().cloned().collect())
+    }
+
+    /// Whether a script logged something, without copying its lines.
+    pub fn has_script_log(&self, id: &ScriptId) -> bool {
+        self.0.logs.borrow().get(id).is_some_and(|log| !log.is_empty())
+    }
+
+    /// Forget the lines logged by a script, when a new session of its game starts.
+    pub fn clear_script_log(&self, id: &ScriptId) {
+        self.0.logs.borrow_mut().remove(id);
+    }
+
+    /// Print the queued lines to `console`, call it every frame.
+    pub fn flush_console(&self, console: &mut Console) {
+        for message in self.0.messages.take() {
+            match message {
+                Message::Log(text) => console.log(text),
+                Message::Note(text) => console.note(text),
+                Message::Warn(text) => console.warn(text),
+                Message::Err(text) => console.err(text),
+            }
+        }
+    }
+}
+
+impl AssetResolver for Context {
+    fn asset_path(&self, path: &str) -> Result<PathBuf, String> {
+        Context::asset_path(self, path)
+    }
+    fn running_script(&self) -> Option<ScriptId> {
+        Context::running_script(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::TempDir;
+    use std::fs;
+
+    fn context(name: &str) -> (TempDir, Context) {
+        let tmp = TempDir::new(name);
+        let ctx = Context::new(&tmp.0);
+        ctx.dirs().create().unwrap();
+        fs::write(ctx.dirs().assets.join("ball.png"), b"").unwrap();
+        fs::write(tmp.0.join("secret.txt"), b"").unwrap();
+        (tmp, ctx)
+    }
+
+    #[test]
+    fn asset_in_assets_folder() {
+        let (_tmp, ctx) = context("asset-found");
+        let path = ctx.asset_path("ball.png").unwrap();
+        assert_eq!(path, ctx.dirs().assets.join("ball.png").canonicalize().unwrap());
+        assert!(ctx.asset_path("missing.png").unwrap_err().contains("not found"));
+    }
+
+    #[test]
+    fn asset_escapes_are_rejected() {
+        let (tmp, ctx) = context("asset-escape");
+        for path in ["../secret.txt", "sub/../../secret.txt", ".."] {
+            let err = ctx.asset_path(path).unwrap_err();
+            assert!(err.contains("Invalid asset path"), "{path}: {err}");
+        }
+        let absolute = tmp.0.join("secret.txt");
+        assert!(ctx.asset_path(absolute).unwrap_err().contains("Invalid asset path"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn asset_symlink_outside_is_rejected() {
+        let (tmp, ctx) = context("asset-symlink");
+        let assets = &ctx.dirs().assets;
+        std::os::unix::fs::symlink(tmp.0.join("secret.txt"), assets.join("link.txt")).unwrap();
+        std::os::unix::fs::symlink(&tmp.0, assets.join("up")).unwrap();
+        std::os::unix::fs::symlink("ball.png", assets.join("alias.png")).unwrap();
+
+        for path in ["link.txt", "up/secret.txt"] {
+            let err = ctx.asset_path(path).unwrap_err();
+            assert!(err.contains("outside of the assets folder"), "{path}: {err}");
+        }
+        // Links inside the folder are fine
+        assert!(ctx.asset_path("alias.png").is_ok());
+    }
+}
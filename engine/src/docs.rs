@@ -0,0 +1,222 @@
+//! Scripting API reference, generated from the [`HostApi`] every backend registers.
+
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use super::*;
+use crate::ffi::{COLORS, KEYS, MOUSE_BUTTONS};
+
+pub const API_MARKDOWN: &str = "API.md";
+pub const API_JSON: &str = "API.json";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FunctionDoc {
+    pub name: String,
+    pub params: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub returns: Option<String>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub doc: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PropertyDoc {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+    pub writable: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TypeDoc {
+    pub name: String,
+    pub properties: Vec<PropertyDoc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConstantDoc {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConstantGroup {
+    pub name: &'static str,
+    pub constants: Vec<ConstantDoc>,
+}
+
+/// Everything a script can use.
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiReference {
+    pub language: &'static str,
+    pub functions: Vec<FunctionDoc>,
+    pub types: Vec<TypeDoc>,
+    pub constants: Vec<ConstantGroup>,
+}
+
+impl ApiReference {
+    /// Create the reference of `api`, which is the same for every backend.
+    pub fn new(language: &'static str, api: &HostApi) -> Self {
+        fn group<T>(
+            name: &'static str,
+            ty: &'static str,
+            table: &[(&'static str, T)],
+        ) -> ConstantGroup {
+            ConstantGroup {
+                name,
+                constants: table
+                    .iter()
+                    .map(|(name, _)| ConstantDoc {
+                        name: (*name).to_owned(),
+                        ty: ty.to_owned(),
+                    })
+                    .collect(),
+            }
+        }
+
+        let functions = api
+            .functions()
+            .iter()
+            .map(|f| FunctionDoc {
+                name: f.name.clone(),
+                params: f.params.iter().map(|(_, ty)| ty.to_string()).collect(),
+                returns: f.returns.map(|ty| ty.to_string()),
+                doc: f.doc.clone(),
+            })
+            .collect();
+        let types = api
+            .types()
+            .iter()
+            .map(|t| TypeDoc {
+                name: t.name.to_owned(),
+                properties: t
+                    .properties
+                    .iter()
+                    .map(|(name, ty, writable)| PropertyDoc {
+                        name: (*name).to_owned(),
+                        ty: ty.to_string(),
+                        writable: *writable,
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        let mut reference = Self {
+            language,
+            functions,
+            types,
+            constants: vec![
+                group("Colors", "Color", &COLORS),
+                group("Keys", "Key", &KEYS),
+                group("Mouse buttons", "Mouse", &MOUSE_BUTTONS),
+            ],
+        };
+        if !api.constants().is_empty() {
+            reference.constants.push(ConstantGroup {
+                name: "Application",
+                constants: api
+                    .constants()
+                    .iter()
+                    .map(|(name, ty, _)| ConstantDoc {
+                        name: name.clone(),
+                        ty: ty.to_string(),
+                    })
+                    .collect(),
+            });
+        }
+        reference.sort();
+        reference
+    }
+
+    /// Sort functions and types by name so the output is stable, and remove duplicates.
+    pub fn sort(&mut self) {
+        self.functions
+            .sort_by(|a, b| (&a.name, &a.params).cmp(&(&b.name, &b.params)));
+        self.functions
+            .dedup_by(|a, b| a.name == b.name && a.params == b.params);
+        self.types.sort_by(|a, b| a.name.cmp(&b.name));
+        for ty in &mut self.types {
+            ty.properties.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("API reference is always serializable")
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::new();
+
+        // NOTE: writing to a String never fails
+        let _ = writeln!(md, "# KGames scripting API ({})\n", self.language);
+        let _ = writeln!(md, "Generated by kgames, do not edit.\n");
+
+        let _ = writeln!(md, "## Functions\n");
+        for f in &self.functions {
+            let _ = write!(md, "- `{}({})", f.name, f.params.join(", "));
+            if let Some(ref returns) = f.returns {
+                let _ = write!(md, " -> {returns}");
+            }
+            let _ = write!(md, "`");
+            if !f.doc.is_empty() {
+                let _ = write!(md, ": {}", f.doc);
+            }
+            let _ = writeln!(md);
+        }
+
+        let _ = writeln!(md, "\n## Types\n");
+        for ty in &self.types {
+            let _ = writeln!(md, "### {}\n", ty.name);
+            if ty.properties.is_empty() {
+                let _ = writeln!(md, "No properties.\n");
+                continue;
+            }
+            let _ = writeln!(md, "| Property | Type | Access |");
+            let _ = writeln!(md, "|----------|------|--------|");
+            for p in &ty.properties {
+                let access = if p.writable { "read/write" } else { "read" };
+                let _ = writeln!(md, "| `{}` | `{}` | {access} |", p.name, p.ty);
+            }
+            let _ = writeln!(md);
+        }
+
+        let _ = writeln!(md, "## Constants\n");
+        for group in &self.constants {
+            let _ = writeln!(md, "### {}\n", group.name);
+            for c in &group.constants {
+                let _ = writeln!(md, "- `{}: {}`", c.name, c.ty);
+            }
+            let _ = writeln!(md);
+        }
+
+        md
+    }
+}
+
+/// Write the API reference (and editor definitions, if the backend has them) to the data folder of `engine`.
+pub fn write_api_reference(engine: &impl ScriptEngine) -> io::Result<Vec<PathBuf>> {
+    let root = &engine.context().dirs().root;
+    let api = engine.api_reference();
+    let mut written = vec![];
+
+    let mut write = |name: &str, contents: String| {
+        let path = root.join(name);
+        fs::write(&path, contents)?;
+        written.push(path);
+        io::Result::Ok(())
+    };
+
+    write(API_MARKDOWN, api.to_markdown())?;
+    write(API_JSON, api.to_json())?;
+
+    if let Some((name, definitions)) = engine.definitions() {
+        write(name, definitions)?;
+    }
+
+    Ok(written)
+}
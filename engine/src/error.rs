@@ -0,0 +1,183 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::assets::{AssetError, AssetKind};
+
+/// A function call leading up to a [`ScriptError`].
+#[derive(Debug, Clone)]
+pub struct StackFrame {
+    pub function: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// An error inside a script, with its position.
+///
+/// `line` and `column` start at 1, and are 0 if the position is unknown.
+/// Stored in an [`EngineError`], as a compile or runtime error.
+#[derive(Debug, Clone)]
+pub struct ScriptError {
+    pub path: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+    /// Outermost call first
+    pub call_stack: Vec<StackFrame>,
+}
+
+impl ScriptError {
+    pub fn new(path: impl Into<PathBuf>, message: impl ToString) -> Self {
+        Self {
+            path: path.into(),
+            line: 0,
+            column: 0,
+            message: message.to_string(),
+            call_stack: vec![],
+        }
+    }
+    pub fn has_position(&self) -> bool {
+        self.line > 0
+    }
+}
+
+/// `{}` prints `file:line:column: message`, `{:#}` adds the call stack.
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        if self.has_position() {
+            write!(f, ":{}:{}", self.line, self.column)?;
+        }
+        write!(f, ": {}", self.message)?;
+
+        if f.alternate() {
+            for frame in self.call_stack.iter().rev() {
+                write!(
+                    f,
+                    "\n    in `{}` at {}:{}",
+                    frame.function, frame.line, frame.column
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// Why the engine failed, stored in an [`ErrorMap`](crate::ErrorMap).
+///
+/// [`ScriptEngine`](crate::ScriptEngine) methods return it inside an `anyhow::Error`, convert it back with `EngineError::from`.
+#[derive(Debug)]
+pub enum EngineError {
+    /// A script or a module it imports does not compile
+    Compile(ScriptError),
+    /// A script failed while running, with the call stack if the backend has one
+    Runtime(ScriptError),
+    /// A script does not define the function it was asked to run
+    MissingHook { path: PathBuf, hook: String },
+    /// A preloaded asset failed to load
+    Asset {
+        path: PathBuf,
+        kind: AssetKind,
+        message: String,
+    },
+    /// Reading or writing a file or folder failed
+    Io { path: PathBuf, error: io::Error },
+    /// Anything else, like a plugin failing to load
+    Other(anyhow::Error),
+}
+
+impl EngineError {
+    pub fn io(path: impl Into<PathBuf>, error: io::Error) -> Self {
+        Self::Io {
+            path: path.into(),
+            error,
+        }
+    }
+
+    /// Short name of the variant, shown with the error.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Compile(_) => "compile error",
+            Self::Runtime(_) => "runtime error",
+            Self::MissingHook { .. } => "missing function",
+            Self::Asset { .. } => "asset error",
+            Self::Io { .. } => "IO error",
+            Self::Other(_) => "error",
+        }
+    }
+
+    /// The error inside a script, with its position, for compile and runtime errors.
+    pub fn script_error(&self) -> Option<&ScriptError> {
+        match self {
+            Self::Compile(e) | Self::Runtime(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    /// The file the error is about, if known.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::Compile(e) | Self::Runtime(e) => Some(&e.path),
+            Self::MissingHook { path, .. } | Self::Asset { path, .. } | Self::Io { path, .. } => Some(path),
+            Self::Other(_) => None,
+        }
+    }
+
+    /// The message without the path, for the error page.
+    pub fn message(&self) -> String {
+        match self {
+            Self::Compile(e) | Self::Runtime(e) => e.message.clone(),
+            Self::MissingHook { hook, .. } => format!("Function not found: {hook}()"),
+            Self::Asset { kind, message, .. } => format!("Failed to load {kind}: {message}"),
+            Self::Io { error, .. } => error.to_string(),
+            Self::Other(e) => format!("{e:#}"),
+        }
+    }
+}
+
+impl From<AssetError> for EngineError {
+    fn from(e: AssetError) -> Self {
+        let AssetError { path, kind, message } = e;
+        Self::Asset { path, kind, message }
+    }
+}
+
+/// Keeps an `EngineError` inside `error`, even under context, anything else becomes [`EngineError::Other`].
+impl From<anyhow::Error> for EngineError {
+    fn from(error: anyhow::Error) -> Self {
+        match error.downcast::<EngineError>() {
+            Ok(e) => e,
+            Err(error) => match error.downcast::<ScriptError>() {
+                // NOTE: backends report compile errors as `EngineError::Compile`
+                Ok(e) => Self::Runtime(e),
+                Err(error) => Self::Other(error),
+            },
+        }
+    }
+}
+
+/// `{}` prints `file:line:column: message` like [`ScriptError`], `{:#}` adds the call stack or the causes.
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Compile(e) | Self::Runtime(e) => fmt::Display::fmt(e, f),
+            Self::Other(e) => fmt::Display::fmt(e, f),
+            _ => {
+                let path = self.path().unwrap_or(Path::new(""));
+                write!(f, "{}: {}", path.display(), self.message())
+            }
+        }
+    }
+}
+
+impl std::error::Error for EngineError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { error, .. } => Some(error),
+            Self::Other(e) => e.source(),
+            _ => None,
+        }
+    }
+}
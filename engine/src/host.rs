@@ -0,0 +1,267 @@
+//! Everything scripts can use, the builtin bindings and what the application embedding the engine adds.
+//!
+//! An application builds a [`HostApi`], adds its functions, types and constants,
+//! and gives it to [`ScriptEngine::expose_api`](crate::ScriptEngine::expose_api) before loading scripts, see `examples/embed.rs`.
+
+use std::rc::Rc;
+
+pub use game_core::bindings::{HostType, HostValue};
+
+use crate::bindings::{ApiType, Binding, Type, Value, BINDINGS, TYPES};
+use crate::ffi::ApiResult;
+use crate::Context;
+
+/// Most parameters a function can have, every backend supports up to this many.
+pub const MAX_PARAMS: usize = 8;
+
+/// Called with the context of the engine and one value per parameter.
+pub type HostFn = Rc<dyn Fn(&Context, Vec<Value>) -> ApiResult<Value>>;
+
+/// A function scripts can call, from [`BINDINGS`] or added with [`HostApi::function`].
+#[derive(Clone)]
+pub struct Function {
+    pub name: String,
+    pub doc: String,
+    pub params: Vec<(String, Type)>,
+    pub returns: Option<Type>,
+    func: HostFn,
+}
+
+impl Function {
+    /// The parameters, as written in the API reference.
+    pub fn signature(&self) -> String {
+        let params: Vec<_> = self
+            .params
+            .iter()
+            .map(|(name, ty)| format!("{name}: {ty}"))
+            .collect();
+        format!("{}({})", self.name, params.join(", "))
+    }
+
+    /// Call with the context of the engine and one value per parameter, of the types in [`Function::params`].
+    pub fn call(&self, ctx: &Context, args: Vec<Value>) -> ApiResult<Value> {
+        (self.func)(ctx, args)
+    }
+}
+
+impl From<&'static Binding> for Function {
+    fn from(b: &'static Binding) -> Self {
+        Self {
+            name: b.name.to_owned(),
+            doc: b.doc.trim().to_owned(),
+            params: b.params.iter().map(|(name, ty)| ((*name).to_owned(), *ty)).collect(),
+            returns: b.returns,
+            func: Rc::new(b.func),
+        }
+    }
+}
+
+/// Closures that can be added with [`HostApi::function`], `Args` are their parameter types.
+/// Implemented for closures with up to [`MAX_PARAMS`] parameters.
+pub trait IntoFunction<Args> {
+    fn params() -> Vec<Type>;
+    fn returns() -> Option<Type>;
+    fn into_function(self) -> HostFn;
+}
+
+macro_rules! into_function {
+    ($($arg:ident),*) => {
+        impl<F, R, $($arg),*> IntoFunction<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> ApiResult<R> + 'static,
+            R: ApiType,
+            $($arg: ApiType,)*
+        {
+            fn params() -> Vec<Type> {
+                vec![$($arg::TYPE),*]
+            }
+            fn returns() -> Option<Type> {
+                (R::TYPE != Type::Unit).then_some(R::TYPE)
+            }
+            #[allow(non_snake_case, unused_mut, unused_variables)]
+            fn into_function(self) -> HostFn {
+                // Functions of the application keep their own state, they do not need the context
+                Rc::new(move |_, args| {
+                    let mut args = args.into_iter();
+                    $(
+                        let $arg = args
+                            .next()
+                            .and_then($arg::from_value)
+                            .ok_or_else(|| format!("wrong type for parameter of type {}", $arg::TYPE))?;
+                    )*
+                    self($($arg),*).map(ApiType::into_value)
+                })
+            }
+        }
+    };
+}
+
+into_function!();
+into_function!(A);
+into_function!(A, B);
+into_function!(A, B, C);
+into_function!(A, B, C, D);
+into_function!(A, B, C, D, E);
+into_function!(A, B, C, D, E, F2);
+into_function!(A, B, C, D, E, F2, G);
+into_function!(A, B, C, D, E, F2, G, H);
+
+/// A type scripts can use, the properties are only known for the builtin ones.
+#[derive(Clone)]
+pub struct TypeDef {
+    pub name: &'static str,
+    /// Name, type and whether it can be assigned
+    pub properties: &'static [(&'static str, Type, bool)],
+}
+
+/// Functions, types and constants given to every backend by [`ScriptEngine::expose_api`](crate::ScriptEngine::expose_api).
+#[derive(Clone)]
+pub struct HostApi {
+    functions: Vec<Function>,
+    types: Vec<TypeDef>,
+    constants: Vec<(String, Type, Value)>,
+}
+
+impl Default for HostApi {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HostApi {
+    /// The builtin API, [`BINDINGS`] and [`TYPES`].
+    pub fn new() -> Self {
+        Self {
+            functions: BINDINGS.iter().map(Function::from).collect(),
+            types: TYPES
+                .iter()
+                .map(|t| TypeDef {
+                    name: t.name,
+                    properties: t.properties,
+                })
+                .collect(),
+            constants: vec![],
+        }
+    }
+
+    /// Add a function, a builtin with the same name keeps working for other arguments.
+    /// Returning an error stops the script like any runtime error.
+    pub fn function<Args, F: IntoFunction<Args>>(&mut self, name: &str, f: F) -> &mut Self {
+        // IntoFunction keeps them within MAX_PARAMS parameters
+        self.push_function(name, F::params(), F::returns(), f.into_function())
+    }
+
+    /// Add a function whose types are only known at runtime, like the ones of plugins.
+    /// `func` is called with values of the types in `params`, there can be at most [`MAX_PARAMS`].
+    pub(crate) fn dynamic_function(
+        &mut self,
+        name: &str,
+        params: Vec<Type>,
+        returns: Option<Type>,
+        func: HostFn,
+    ) -> Result<&mut Self, String> {
+        if params.len() > MAX_PARAMS {
+            return Err(format!(
+                "{name}() has {} parameters, functions can have at most {MAX_PARAMS}",
+                params.len()
+            ));
+        }
+        Ok(self.push_function(name, params, returns, func))
+    }
+
+    fn push_function(
+        &mut self,
+        name: &str,
+        params: Vec<Type>,
+        returns: Option<Type>,
+        func: HostFn,
+    ) -> &mut Self {
+        let params = params
+            .into_iter()
+            .enumerate()
+            .map(|(i, ty)| (format!("arg{}", i + 1), ty))
+            .collect();
+
+        self.functions.push(Function {
+            name: name.to_owned(),
+            doc: String::new(),
+            params,
+            returns,
+            func,
+        });
+        self
+    }
+
+    /// Add a type, so it is listed in the API reference and editor definitions.
+    pub fn host_type<T: HostType>(&mut self) -> &mut Self {
+        if !self.types.iter().any(|t| t.name == T::NAME) {
+            self.types.push(TypeDef {
+                name: T::NAME,
+                properties: &[],
+            });
+        }
+        self
+    }
+
+    /// Add a global constant, next to the builtin colors and keys.
+    pub fn constant<T: ApiType>(&mut self, name: &str, value: T) -> &mut Self {
+        self.dynamic_constant(name, T::TYPE, value.into_value())
+    }
+
+    /// Add a constant whose type is only known at runtime, `value` must be of type `ty`.
+    pub(crate) fn dynamic_constant(&mut self, name: &str, ty: Type, value: Value) -> &mut Self {
+        self.constants.push((name.to_owned(), ty, value));
+        self
+    }
+
+    pub fn functions(&self) -> &[Function] {
+        &self.functions
+    }
+    pub fn types(&self) -> &[TypeDef] {
+        &self.types
+    }
+    pub fn constants(&self) -> &[(String, Type, Value)] {
+        &self.constants
+    }
+
+    /// Functions sharing `name`, builtins first.
+    pub fn overloads<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Function> + 'a {
+        self.functions.iter().filter(move |f| f.name == name)
+    }
+
+    /// Names of all the functions, without duplicates.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<_> = self.functions.iter().map(|f| f.name.as_str()).collect();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    /// Call the first function named `name` whose parameters accept `args`, with the context of the engine.
+    /// `convert` turns an argument of a backend into a value of the parameter type, `None` if it has another type.
+    pub fn call<A>(
+        &self,
+        ctx: &Context,
+        name: &str,
+        args: &[A],
+        convert: impl Fn(&A, Type) -> Option<Value>,
+    ) -> ApiResult<Value> {
+        for function in self.overloads(name).filter(|f| f.params.len() == args.len()) {
+            let values: Option<Vec<_>> = args
+                .iter()
+                .zip(&function.params)
+                .map(|(arg, (_, ty))| convert(arg, *ty))
+                .collect();
+            if let Some(values) = values {
+                return function.call(ctx, values);
+            }
+        }
+
+        let expected: Vec<_> = self.overloads(name).map(Function::signature).collect();
+        Err(format!(
+            "{name}() can not be called with these {} arguments, expected {}",
+            args.len(),
+            expected.join(" or ")
+        ))
+    }
+}
@@ -1,90 +1,120 @@
 mod common;
-mod engine;
-
+mod context;
+mod error;
+
+pub mod bindings;
+pub mod check;
+#[cfg(test)]
+mod conformance;
+pub mod docs;
 pub mod ffi;
-pub mod texture;
+pub mod host;
+pub mod plugins;
+pub mod watch;
 
 #[cfg(feature = "rhai-engine")]
 mod rhai_engine;
+#[cfg(feature = "rhai-engine")]
+mod rhai_modules;
 
+#[cfg(feature = "lua-engine")]
+mod lua_check;
 #[cfg(feature = "lua-engine")]
 mod lua_engine;
 
+#[cfg(feature = "wasm-engine")]
+mod wasm_engine;
+
+mod multi_engine;
+
+#[cfg(not(any(feature = "rhai-engine", feature = "lua-engine", feature = "wasm-engine")))]
+compile_error!("At least one scripting backend is needed, enable `rhai-engine`, `lua-engine` or `wasm-engine`");
+
+pub use check::Diagnostic;
+pub use docs::ApiReference;
+pub use host::{HostApi, HostType};
 pub use common::*;
-pub use engine::*;
-use std::fmt::Display;
+pub use context::{Context, LogLevel, LogLine};
+pub use game_core::{assets, texture, ScriptId};
+pub use error::*;
 
 use std::path::Path;
-use std::{
-    cell::{LazyCell, OnceCell},
-    io,
-    path::PathBuf,
-};
+use std::{io, path::PathBuf};
 
 pub const IS_RHAI: bool = cfg!(feature = "rhai-engine");
 pub const IS_LUA: bool = cfg!(feature = "lua-engine");
-
-// Types
-#[cfg(feature = "rhai-engine")]
-pub mod scripting {
-    pub use rhai::{EvalAltResult, ImmutableString};
-    pub type Error = Box<EvalAltResult>;
-    pub type Result<T> = std::result::Result<T, Error>;
-    pub use super::rhai_engine::Engine;
-}
-
-#[cfg(feature = "lua-engine")]
-pub mod scripting {
-    pub type Error = mlua::Error;
-    pub type Result<T> = std::result::Result<T, Error>;
-    pub use super::lua_engine::Engine;
-}
-
-pub use scripting::Engine;
-
-pub fn external_error(e: impl ToString + Display) -> scripting::Error {
-    use scripting::*;
-    #[cfg(feature = "rhai-engine")]
-    return Box::new(EvalAltResult::from(e.to_string()));
-    #[cfg(feature = "lua-engine")]
-    return scripting::Error::external(anyhow::anyhow!("{e}"));
-}
-
-pub const ENGINE_NAME: &str = if cfg!(feature = "rhai-engine") {
-    "rhai"
-} else if cfg!(feature = "lua-engine") {
-    "lua"
-} else {
-    unimplemented!()
+pub const IS_WASM: bool = cfg!(feature = "wasm-engine");
+
+/// Runs the scripts of every enabled backend, each file goes to the backend of its extension.
+pub use multi_engine::Engine;
+
+pub const ENGINE_NAME: &str = match (IS_RHAI, IS_LUA, IS_WASM) {
+    (true, true, true) => "rhai + lua + wasm",
+    (true, true, false) => "rhai + lua",
+    (true, false, true) => "rhai + wasm",
+    (true, false, false) => "rhai",
+    (false, true, true) => "lua + wasm",
+    (false, true, false) => "lua",
+    _ => "wasm",
 };
 
+pub use config::Limits;
 use console::Console;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum ScriptDir {
     Scripts,
     Examples,
+    /// Modules imported by scripts, never loaded as games
+    Lib,
 }
 impl ScriptDir {
-    pub fn path(self) -> &'static Path {
+    pub fn path(self, dirs: &Dirs) -> &Path {
         match self {
-            Self::Scripts => &dirs().scripts,
-            Self::Examples => &dirs().examples,
+            Self::Scripts => &dirs.scripts,
+            Self::Examples => &dirs.examples,
+            Self::Lib => &dirs.lib,
         }
     }
 }
 
-pub type ErrorMap = Vec<(PathBuf, anyhow::Error)>;
+pub type ErrorMap = Vec<(PathBuf, EngineError)>;
+
+/// Find a script by id, failing if it was removed.
+pub fn find_script<'s, S: GameScript + ?Sized>(
+    scripts: impl IntoIterator<Item = &'s mut S>,
+    id: &ScriptId,
+) -> anyhow::Result<&'s mut S> {
+    scripts
+        .into_iter()
+        .find(|s| s.id() == id)
+        .ok_or_else(|| anyhow::anyhow!("No script with id {id}, it may have been deleted"))
+}
 
 pub trait ScriptEngine {
-    type Script: GameScript;
+    type Script: GameScript + ?Sized;
 
-    fn extension<'a>() -> &'a str;
-    fn expose_layer(&mut self);
+    /// Extensions of the scripts handled by the engine, without the dot.
+    fn extensions() -> &'static [&'static str];
+    /// Extension of new scripts and examples.
+    fn extension() -> &'static str {
+        Self::extensions()[0]
+    }
+    /// The context the engine was created with, shared with the functions called by scripts.
+    fn context(&self) -> &Context;
+    /// Register the builtin functions, types and constants.
+    fn expose_layer(&mut self) {
+        self.expose_api(&HostApi::new());
+    }
+    /// Register `api` instead, with what the embedding application adds. Call before loading scripts.
+    fn expose_api(&mut self, api: &HostApi);
+    /// Limit what scripts can do, a call exceeding a limit fails with an error.
+    fn set_limits(&mut self, limits: &Limits);
     fn write_examples(&mut self, warnings: &mut Vec<String>) -> Result<(), Vec<io::Error>>;
 
-    fn scripts<'a>(&'a mut self) -> &'a mut [Self::Script];
+    fn scripts<'a>(&'a mut self) -> Vec<&'a mut Self::Script>;
 
+    /// Compile new and changed scripts in `from` and its subfolders, and drop the scripts that are gone.
     fn load_scripts(
         &mut self,
         console: &mut Console,
@@ -92,21 +122,81 @@ pub trait ScriptEngine {
         from: &[ScriptDir],
     ) -> anyhow::Result<()>;
 
-    fn call_function(&mut self, script_index: usize, name: impl AsRef<str>) -> anyhow::Result<()>;
+    fn script(&mut self, id: &ScriptId) -> anyhow::Result<&mut Self::Script> {
+        find_script(self.scripts(), id)
+    }
+
+    fn call_function(&mut self, id: &ScriptId, name: impl AsRef<str>) -> anyhow::Result<()>;
+
+    /// Whether a script defines a function without arguments, for optional hooks.
+    fn has_function(&mut self, id: &ScriptId, name: &str) -> bool;
+
+    /// Reset the state of a script and run its top level code again.
+    fn restart_script(&mut self, id: &ScriptId) -> anyhow::Result<()>;
+
+    /// Reset the state of a script and free the assets only it uses, when its game is exited.
+    fn stop_script(&mut self, id: &ScriptId) -> anyhow::Result<()> {
+        self.script(id)?.reset()?;
+        self.context().assets_mut().release(id);
+        Ok(())
+    }
 
+    /// Compile a script without running it, used by the headless tooling.
+    /// `source` is the contents of the file, which is not text for every backend.
+    fn check_script(&self, path: &Path, source: &[u8]) -> Vec<Diagnostic>;
+
+    /// Everything registered by `expose_layer`, used to generate documentation.
+    fn api_reference(&self) -> ApiReference;
+
+    /// File name and contents of editor definitions for autocompletion, if supported.
+    fn definitions(&self) -> Option<(&'static str, String)> {
+        None
+    }
+
+    /// Load all the scripts again, dropping those whose file was deleted.
     fn reload_scripts(
         &mut self,
         console: &mut Console,
         errors: &mut ErrorMap,
     ) -> anyhow::Result<()> {
-        Ok(())
+        self.load_scripts(console, errors, &[ScriptDir::Examples, ScriptDir::Scripts])
+    }
+
+    /// Reload after the files in `changed` were added, modified or removed,
+    /// as reported by [`ScriptWatcher::poll`](watch::ScriptWatcher::poll).
+    /// Only the scripts that changed are compiled and run again.
+    fn reload_changed(
+        &mut self,
+        console: &mut Console,
+        errors: &mut ErrorMap,
+        _changed: &[PathBuf],
+    ) -> anyhow::Result<()> {
+        // Unchanged scripts are skipped by their modification time
+        self.reload_scripts(console, errors)
+    }
+
+    /// Scripts renamed by the last load, as `(old, new)` ids.
+    /// A renamed script keeps its state.
+    fn renamed_scripts(&self) -> &[(ScriptId, ScriptId)] {
+        &[]
+    }
+
+    /// Scripts whose top level code ran during the last load: the new and the changed ones.
+    fn loaded_scripts(&self) -> &[ScriptId] {
+        &[]
     }
 }
 
 pub trait GameScript {
+    fn id<'a>(&'a self) -> &'a ScriptId;
     fn path<'a>(&'a self) -> &'a Path;
     fn name<'a>(&'a self) -> Option<&'a str>;
+    /// Subfolders the script is in, see [`FoundScript::category`]
+    fn category<'a>(&'a self) -> &'a str;
+    /// Folder of a folder-per-game, its assets are resolved relative to it
+    fn game_dir<'a>(&'a self) -> Option<&'a Path>;
     fn is_example(&self) -> bool;
-    fn reset(&mut self);
+    /// Drop the state of the script, fails if the backend can not create a new one.
+    fn reset(&mut self) -> anyhow::Result<()>;
     fn populate_scope(&mut self);
 }
@@ -0,0 +1,306 @@
+//! What `check_script` of the Lua backend finds without running a script:
+//! the hooks it defines and its calls to functions that do not exist.
+//!
+//! Lua has no declarations, so this only reads the tokens of the source.
+//! It errs on the side of missing an unknown call rather than reporting a known one.
+
+use std::collections::HashSet;
+
+/// Words of Lua which can be followed by `(` without being called.
+pub(crate) const KEYWORDS: [&str; 22] = [
+    "and", "break", "continue", "do", "else", "elseif", "end", "false", "for", "function", "if", "in",
+    "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+/// A token of a Lua source.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Token {
+    Name(String),
+    Punct(char),
+    /// Strings, numbers and operators of several characters
+    Other,
+}
+
+/// Split `source` into tokens with their line and column, comments are skipped.
+pub(crate) fn lua_tokens(source: &str) -> Vec<(Token, usize, usize)> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = vec![];
+    let (mut i, mut line, mut line_start) = (0, 1, 0);
+
+    // Skip a long bracket `[==[ ... ]==]` starting at `i`, if there is one
+    let long_bracket = |i: usize| -> Option<usize> {
+        let level = chars[i + 1..].iter().take_while(|c| **c == '=').count();
+        if chars.get(i + 1 + level) != Some(&'[') {
+            return None;
+        }
+        let close: Vec<char> = std::iter::once(']')
+            .chain(std::iter::repeat_n('=', level))
+            .chain(std::iter::once(']'))
+            .collect();
+        let start = i + 2 + level;
+        let end = (start..chars.len())
+            .find(|j| chars[*j..].starts_with(&close))
+            .map_or(chars.len(), |j| j + close.len());
+        Some(end)
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+        let column = i - line_start + 1;
+        let start = i;
+
+        if c == '-' && chars.get(i + 1) == Some(&'-') {
+            i = match (chars.get(i + 2) == Some(&'[')).then(|| long_bracket(i + 2)).flatten() {
+                Some(end) => end,
+                None => (i..chars.len()).find(|j| chars[*j] == '\n').unwrap_or(chars.len()),
+            };
+        } else if c == '[' && long_bracket(i).is_some() {
+            i = long_bracket(i).unwrap_or(chars.len());
+            tokens.push((Token::Other, line, column));
+        } else if c == '"' || c == '\'' || c == '`' {
+            i += 1;
+            while i < chars.len() && chars[i] != c && chars[i] != '\n' {
+                i += if chars[i] == '\\' { 2 } else { 1 };
+            }
+            i += 1;
+            tokens.push((Token::Other, line, column));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push((Token::Name(chars[start..i].iter().collect()), line, column));
+        } else if c.is_ascii_digit() {
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.' || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push((Token::Other, line, column));
+        } else if c == '.' && chars.get(i + 1) == Some(&'.') {
+            while chars.get(i) == Some(&'.') {
+                i += 1;
+            }
+            tokens.push((Token::Other, line, column));
+        } else if "=~<>".contains(c) && chars.get(i + 1) == Some(&'=') {
+            i += 2;
+            tokens.push((Token::Other, line, column));
+        } else {
+            i += 1;
+            if !c.is_whitespace() {
+                tokens.push((Token::Punct(c), line, column));
+            }
+        }
+
+        // Strings and comments can span lines
+        for (j, c) in chars.iter().enumerate().take(i.min(chars.len())).skip(start) {
+            if *c == '\n' {
+                line += 1;
+                line_start = j + 1;
+            }
+        }
+    }
+    tokens
+}
+
+/// Names a script defines as functions, locals, parameters or globals,
+/// and the hooks: global functions without parameters.
+/// Fields of tables are neither, even when they have the name of a hook or of the API.
+pub(crate) fn lua_definitions(tokens: &[(Token, usize, usize)]) -> (HashSet<&str>, Vec<&str>) {
+    let name = |i: usize| match tokens.get(i) {
+        Some((Token::Name(name), ..)) if !KEYWORDS.contains(&name.as_str()) => Some(name.as_str()),
+        _ => None,
+    };
+    let punct = |i: usize, c: char| tokens.get(i).is_some_and(|(t, ..)| *t == Token::Punct(c));
+    let keyword = |i: usize, word: &str| {
+        tokens.get(i).is_some_and(|(t, ..)| matches!(t, Token::Name(n) if n == word))
+    };
+    let is_field = |i: usize| i > 0 && (punct(i - 1, '.') || punct(i - 1, ':'));
+    let (mut defined, mut hooks) = (HashSet::new(), vec![]);
+    // Whether each open block is a table constructor
+    let mut tables = vec![];
+
+    for i in 0..tokens.len() {
+        match &tokens[i].0 {
+            Token::Punct('{') => tables.push(true),
+            Token::Punct('}') => _ = tables.pop(),
+            Token::Name(n) if matches!(n.as_str(), "function" | "do" | "if" | "repeat") => {
+                tables.push(false)
+            }
+            Token::Name(n) if matches!(n.as_str(), "end" | "until") => _ = tables.pop(),
+            _ => {}
+        }
+        let in_table = tables.last() == Some(&true);
+
+        if keyword(i, "local") || keyword(i, "for") {
+            // `local a, b = ...`, `for k, v in ...`
+            let mut j = i + 1;
+            while let Some(name) = name(j) {
+                defined.insert(name);
+                if !punct(j + 1, ',') {
+                    break;
+                }
+                j += 2;
+            }
+        } else if keyword(i, "function") {
+            let mut j = i + 1;
+            if let Some(function) = name(j) {
+                defined.insert(function);
+                // `function a.b:c()` defines a field
+                let is_method = punct(j + 1, '.') || punct(j + 1, ':');
+                let is_hook = !is_method && !keyword(i.wrapping_sub(1), "local") && punct(j + 1, '(') && punct(j + 2, ')');
+                if is_hook {
+                    hooks.push(function);
+                }
+                while punct(j + 1, '.') || punct(j + 1, ':') {
+                    j += 2;
+                }
+                j += 1;
+            }
+            // Parameters, with their Luau type if they have one
+            if punct(j, '(') {
+                j += 1;
+                while let Some(param) = name(j) {
+                    defined.insert(param);
+                    while j < tokens.len() && !punct(j, ',') && !punct(j, ')') {
+                        j += 1;
+                    }
+                    j += 1;
+                }
+            }
+        } else if name(i).is_some() && !in_table && !is_field(i) {
+            // `a, b = ...` at the start of a statement, not `t.a = ...` or `{a = ...}`
+            let mut j = i;
+            while punct(j + 1, ',') && name(j + 2).is_some() {
+                j += 2;
+            }
+            if punct(j + 1, '=') {
+                defined.extend((i..=j).step_by(2).filter_map(name));
+                if i == j && keyword(i + 2, "function") && punct(i + 3, '(') && punct(i + 4, ')') {
+                    hooks.extend(name(i));
+                }
+            }
+        }
+    }
+    (defined, hooks)
+}
+
+/// Calls to names that are not keywords, not defined by the script and not in `globals`,
+/// like the API and the standard library, with their line and column.
+pub(crate) fn unknown_calls<'t>(
+    tokens: &'t [(Token, usize, usize)],
+    defined: &HashSet<&str>,
+    globals: &HashSet<String>,
+) -> Vec<(&'t str, usize, usize)> {
+    let previous = |i: usize| i.checked_sub(1).map(|i| &tokens[i].0);
+
+    tokens
+        .iter()
+        .enumerate()
+        .filter_map(|(i, (token, line, column))| {
+            let Token::Name(name) = token else {
+                return None;
+            };
+            let is_call = tokens.get(i + 1).is_some_and(|(t, ..)| *t == Token::Punct('('));
+            let is_field = matches!(previous(i), Some(Token::Punct('.') | Token::Punct(':')));
+            let is_definition = matches!(previous(i), Some(Token::Name(n)) if n == "function");
+            let known = KEYWORDS.contains(&name.as_str())
+                || defined.contains(name.as_str())
+                || globals.contains(name);
+
+            (is_call && !is_field && !is_definition && !known).then_some((name.as_str(), *line, *column))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unknown(source: &str, globals: &[&str]) -> Vec<(String, usize, usize)> {
+        let tokens = lua_tokens(source);
+        let (defined, _) = lua_definitions(&tokens);
+        let globals = globals.iter().map(|g| g.to_string()).collect();
+        unknown_calls(&tokens, &defined, &globals)
+            .into_iter()
+            .map(|(name, line, column)| (name.to_owned(), line, column))
+            .collect()
+    }
+
+    fn hooks(source: &str) -> Vec<String> {
+        let tokens = lua_tokens(source);
+        lua_definitions(&tokens).1.into_iter().map(str::to_owned).collect()
+    }
+
+    #[test]
+    fn unknown_call_position() {
+        let source = "local x = 1\n  missing(x)\nprint(x)";
+        assert_eq!(unknown(source, &["print"]), [("missing".to_owned(), 2, 3)]);
+    }
+
+    #[test]
+    fn long_strings_and_comments() {
+        let source = r#"
+local s = [==[ a() ]] b() ]==]
+local t = [[
+c()]]
+--[[ d()
+]] --[==[ ]] e() ]==]
+---[[ f()
+-- g()
+local q = "h()" .. 'i(\'j()\')'
+late()
+"#;
+        assert_eq!(unknown(source, &[]), [("late".to_owned(), 10, 1)]);
+    }
+
+    #[test]
+    fn method_calls() {
+        let source = "local p = {}\nfunction p:move(dx) self.x = dx end\np:move(1)\np.move(p, 2)";
+        assert!(unknown(source, &[]).is_empty());
+    }
+
+    #[test]
+    fn local_functions() {
+        let source = "local function helper(a: number, b: string) b() end\nhelper(1, 'x')";
+        assert!(unknown(source, &[]).is_empty());
+        assert!(hooks(source).is_empty());
+    }
+
+    #[test]
+    fn hooks_are_global_functions_without_parameters() {
+        let source = "
+function update() end
+draw = function() end
+function M.init() end
+local function reset() end
+function step(dt) end
+";
+        assert_eq!(hooks(source), ["update", "draw"]);
+    }
+
+    #[test]
+    fn table_fields_are_not_globals() {
+        let source = "
+local ui = {
+    draw = function() helper() end,
+    update = 1,
+    clear = function()
+        if true then x = 1 end
+    end,
+}
+ui.clear()
+clear()
+";
+        assert!(hooks(source).is_empty());
+        assert_eq!(
+            unknown(source, &[]),
+            [("helper".to_owned(), 3, 23), ("clear".to_owned(), 10, 1)]
+        );
+        // Fields named like the API do not hide it
+        assert_eq!(unknown(source, &["clear"]), [("helper".to_owned(), 3, 23)]);
+    }
+
+    #[test]
+    fn multiple_assignment() {
+        let source = "a, b = print, print\na()\nb()\nfor k, v in pairs({}) do k() v() end";
+        assert!(unknown(source, &["print", "pairs"]).is_empty());
+    }
+}
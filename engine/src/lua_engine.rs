@@ -1,144 +1,494 @@
-use std::ffi::OsStr;
-use std::path::Path;
-use std::path::PathBuf;
-use std::time::SystemTime;
-
-use mlua::Chunk;
-use mlua::Compiler;
-use mlua::Function;
-use mlua::Lua;
-use mlua::Scope;
-
-use crate::ffi;
-use crate::ffi::COLORS;
+use std::collections::HashSet;
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, SystemTime};
+
 use macroquad::prelude::*;
+use mlua::{
+    ChunkMode, Compiler, Function, Lua, MultiValue, Table, UserData, UserDataFields, Value, VmState,
+};
+
+use crate::assets::{FontHandle, SoundHandle};
+use crate::bindings::{self, Type};
+use crate::ffi::{self, COLORS};
+use crate::host::{HostApi, HostValue};
+use crate::lua_check;
 
 use super::*;
 
-macro_rules! readonly_table {
-    ($lua:expr, $type_:ty {$($field:ident),+}) => {
-        {
-            use super::*;
-            |__x: $type_| $lua.create_table_from([
-                $(
-                    (stringify!($field), __x.$field),
-                )+
-            ]).and_then(|t| {
-                t.set_readonly(true);
-                Ok(t)
-            })
+/// Texture given to scripts, `width` and `height` can be read.
+#[derive(Clone)]
+struct LuaTexture(Texture2D);
+
+impl UserData for LuaTexture {
+    fn add_fields<'lua, F: UserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_field_method_get("width", |_, t| Ok(t.0.width()));
+        fields.add_field_method_get("height", |_, t| Ok(t.0.height()));
+    }
+}
+
+#[derive(Clone)]
+struct LuaFont(FontHandle);
+
+impl UserData for LuaFont {}
+
+#[derive(Clone)]
+struct LuaSound(SoundHandle);
+
+impl UserData for LuaSound {}
+
+#[derive(Clone)]
+struct LuaHost(HostValue);
+
+impl UserData for LuaHost {}
+
+/// Runtime error raised in the script, for failures of the functions it calls.
+fn external_error(e: impl ToString) -> mlua::Error {
+    mlua::Error::RuntimeError(e.to_string())
+}
+
+// Conversions, vectors, rects and colors are plain tables
+fn vec2_table(lua: &Lua, v: Vec2) -> mlua::Result<Table<'_>> {
+    lua.create_table_from([("x", v.x), ("y", v.y)])
+}
+fn vec3_table(lua: &Lua, v: Vec3) -> mlua::Result<Table<'_>> {
+    lua.create_table_from([("x", v.x), ("y", v.y), ("z", v.z)])
+}
+fn rect_table(lua: &Lua, r: Rect) -> mlua::Result<Table<'_>> {
+    lua.create_table_from([("x", r.x), ("y", r.y), ("w", r.w), ("h", r.h)])
+}
+fn color_table(lua: &Lua, c: Color) -> mlua::Result<Table<'_>> {
+    lua.create_table_from([("r", c.r), ("g", c.g), ("b", c.b), ("a", c.a)])
+}
+fn to_vec2(t: &Table) -> mlua::Result<Vec2> {
+    Ok(vec2(t.get("x")?, t.get("y")?))
+}
+fn to_vec3(t: &Table) -> mlua::Result<Vec3> {
+    Ok(vec3(t.get("x")?, t.get("y")?, t.get("z")?))
+}
+fn to_rect(t: &Table) -> mlua::Result<Rect> {
+    Ok(Rect::new(t.get("x")?, t.get("y")?, t.get("w")?, t.get("h")?))
+}
+fn to_color(t: &Table) -> mlua::Result<Color> {
+    Ok(Color::new(t.get("r")?, t.get("g")?, t.get("b")?, t.get("a")?))
+}
+fn to_key(code: u16) -> mlua::Result<KeyCode> {
+    ffi::KEYS
+        .iter()
+        .find(|(_, key)| *key as u16 == code)
+        .map(|(_, key)| *key)
+        .ok_or_else(|| external_error(format!("Unknown key {code}")))
+}
+fn to_mouse_button(code: u8) -> mlua::Result<MouseButton> {
+    ffi::MOUSE_BUTTONS
+        .iter()
+        .find(|(_, button)| *button as u8 == code)
+        .map(|(_, button)| *button)
+        .ok_or_else(|| external_error(format!("Unknown mouse button {code}")))
+}
+
+/// Convert an argument for a parameter of type `ty`.
+fn to_value(arg: &Value, ty: Type) -> Option<bindings::Value> {
+    use bindings::Value as V;
+
+    let number = match *arg {
+        Value::Integer(n) => Some(n as f64),
+        Value::Number(n) => Some(n),
+        _ => None,
+    };
+    let value = match (ty, arg) {
+        (Type::Unit, Value::Nil) => V::Unit,
+        (Type::Float, _) => V::Float(number? as f32),
+        (Type::Int, _) => V::Int(number? as i64),
+        (Type::Bool, Value::Boolean(b)) => V::Bool(*b),
+        (Type::String, Value::String(s)) => V::String(s.to_str().ok()?.to_owned()),
+        (Type::Strings, Value::Table(t)) => V::Strings(t.clone().sequence_values().collect::<mlua::Result<_>>().ok()?),
+        (Type::Vec2, Value::Table(t)) => V::Vec2(to_vec2(t).ok()?),
+        (Type::Vec3, Value::Table(t)) => V::Vec3(to_vec3(t).ok()?),
+        (Type::Rect, Value::Table(t)) => V::Rect(to_rect(t).ok()?),
+        (Type::Color, Value::Table(t)) => V::Color(to_color(t).ok()?),
+        (Type::Texture, Value::UserData(u)) => V::Texture(u.borrow::<LuaTexture>().ok()?.0.clone()),
+        (Type::Sound, Value::UserData(u)) => V::Sound(u.borrow::<LuaSound>().ok()?.0.clone()),
+        (Type::Font, Value::UserData(u)) => V::Font(u.borrow::<LuaFont>().ok()?.0.clone()),
+        (Type::Key, _) => V::Key(to_key(number? as u16).ok()?),
+        (Type::Mouse, _) => V::Mouse(to_mouse_button(number? as u8).ok()?),
+        (Type::Host(name), Value::UserData(u)) => {
+            V::Host(u.borrow::<LuaHost>().ok().filter(|v| v.0.name == name)?.0.clone())
+        }
+        (Type::Optional(_), Value::Nil) => V::Unit,
+        (Type::Optional(ty), _) => to_value(arg, *ty)?,
+        _ => return None,
+    };
+    Some(value)
+}
+
+fn from_value(lua: &Lua, value: bindings::Value) -> mlua::Result<Value<'_>> {
+    use bindings::Value as V;
+
+    Ok(match value {
+        V::Unit => Value::Nil,
+        V::Float(v) => Value::Number(v as f64),
+        V::Int(v) => Value::Number(v as f64),
+        V::Bool(v) => Value::Boolean(v),
+        V::String(v) => Value::String(lua.create_string(&v)?),
+        V::Strings(v) => Value::Table(lua.create_sequence_from(v)?),
+        V::Vec2(v) => Value::Table(vec2_table(lua, v)?),
+        V::Vec3(v) => Value::Table(vec3_table(lua, v)?),
+        V::Rect(v) => Value::Table(rect_table(lua, v)?),
+        V::Color(v) => Value::Table(color_table(lua, v)?),
+        V::Texture(v) => Value::UserData(lua.create_userdata(LuaTexture(v))?),
+        V::Sound(v) => Value::UserData(lua.create_userdata(LuaSound(v))?),
+        V::Font(v) => Value::UserData(lua.create_userdata(LuaFont(v))?),
+        V::Key(v) => Value::Number(v as u16 as f64),
+        V::Mouse(v) => Value::Number(v as u8 as f64),
+        V::Host(v) => Value::UserData(lua.create_userdata(LuaHost(v))?),
+    })
+}
+
+/// The context of the engine running a script, set as the app data of its VM.
+fn context(lua: &Lua) -> mlua::Result<Context> {
+    lua.app_data_ref::<Context>()
+        .map(|ctx| ctx.clone())
+        .ok_or_else(|| external_error("the engine context is missing"))
+}
+
+/// Register every function of `api` as a global function, overloads are resolved on each call.
+/// Builtins of Lua are kept, `error` still raises an error instead of logging it.
+fn register_api(lua: &Lua, api: &Rc<HostApi>) -> mlua::Result<()> {
+    let globals = lua.globals();
+
+    for name in api.names() {
+        if globals.contains_key(name)? {
+            continue;
+        }
+        let (api, function) = (api.clone(), name.to_owned());
+        let function = lua.create_function(move |lua, args: MultiValue| {
+            let value = api
+                .call(&context(lua)?, &function, &args.into_vec(), to_value)
+                .map_err(external_error)?;
+            from_value(lua, value)
+        })?;
+        globals.set(name, function)?;
+    }
+
+    Ok(())
+}
+
+/// Make a table of constants read only.
+fn readonly<'lua>(t: mlua::Result<Table<'lua>>) -> mlua::Result<Table<'lua>> {
+    t.map(|t| {
+        t.set_readonly(true);
+        t
+    })
+}
+
+/// A new VM for a script, with the API if it was exposed.
+/// Functions get `ctx` from the app data of the VM.
+fn new_vm(
+    ctx: &Context,
+    api: Option<&Rc<HostApi>>,
+    watchdog: &Rc<Watchdog>,
+    max_memory: usize,
+) -> mlua::Result<Lua> {
+    let vm = Lua::new();
+    vm.set_memory_limit(max_memory)?;
+    vm.set_app_data(ctx.clone());
+    if let Some(api) = api {
+        register_api(&vm, api)?;
+    }
+    // Logged for the running script instead of stdout, which release builds on Windows do not have
+    let print = vm.create_function(|lua, args: MultiValue| {
+        let text: Vec<_> = args.iter().map(Value::to_string).collect::<mlua::Result<_>>()?;
+        context(lua)?.script_log(LogLevel::Info, text.join("\t"));
+        Ok(())
+    })?;
+    vm.globals().set("print", print)?;
+
+    // Luau interrupts at function calls and loop iterations, which are the operations it counts
+    let wd = watchdog.clone();
+    vm.set_interrupt(move |_| {
+        if wd.expired() {
+            return Err(mlua::Error::RuntimeError(wd.abort_message()));
+        }
+        if !wd.count_operation() {
+            return Err(mlua::Error::RuntimeError(wd.operations_message()));
         }
+        Ok(VmState::Continue)
+    });
+    Ok(vm)
+}
+
+/// Name of the chunk of a script, Luau prefixes error messages with it.
+/// It is given with a leading `=`, or Luau shows it as `[string "name"]`.
+fn chunk_name(path: &Path) -> String {
+    path.file_name()
+        .map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().into_owned())
+}
+
+/// Turn a Lua error into a [`ScriptError`], with the line if the message starts with `chunk:line:`.
+fn lua_error(path: &Path, err: mlua::Error) -> ScriptError {
+    let message = match err {
+        mlua::Error::CallbackError { cause, .. } => return lua_error(path, (*cause).clone()),
+        mlua::Error::SyntaxError { message, .. } => message,
+        mlua::Error::RuntimeError(message) => message,
+        err => err.to_string(),
     };
+
+    let prefix = format!("{}:", chunk_name(path));
+    let position = message
+        .strip_prefix(&prefix)
+        .and_then(|rest| rest.split_once(": "))
+        .and_then(|(line, rest)| Some((line.parse().ok()?, rest)));
+
+    match position {
+        Some((line, rest)) => ScriptError {
+            line,
+            ..ScriptError::new(path, rest)
+        },
+        None => ScriptError::new(path, message),
+    }
+}
+
+/// A compile error for syntax errors, which Luau only reports when the bytecode is loaded, a runtime error otherwise.
+fn engine_error(path: &Path, err: mlua::Error) -> EngineError {
+    match err {
+        mlua::Error::SyntaxError { .. } => EngineError::Compile(lua_error(path, err)),
+        err => EngineError::Runtime(lua_error(path, err)),
+    }
 }
 
 pub struct Engine {
     scripts: Vec<Script>,
     compiler: Compiler,
+    renamed: Vec<(ScriptId, ScriptId)>,
+    /// Scripts whose top level code ran during the last load
+    loaded: Vec<ScriptId>,
+    /// Bytes each VM may allocate, 0 for no limit
+    max_memory: usize,
+    /// Set by `expose_api`, new VMs only get the API then
+    api: Option<Rc<HostApi>>,
+    watchdog: Rc<Watchdog>,
+    ctx: Context,
 }
 
 impl Engine {
-    pub fn new() -> Self {
-        Self {
+    pub fn new(ctx: Context) -> Self {
+        let mut engine = Self {
             scripts: vec![],
             compiler: Compiler::new(),
-        }
+            renamed: vec![],
+            loaded: vec![],
+            max_memory: 0,
+            api: None,
+            watchdog: Rc::new(Watchdog::default()),
+            ctx,
+        };
+        engine.set_limits(&Limits::default());
+        engine
     }
+
     fn compile_and_init_script(
         &self,
-        path: PathBuf,
-        src: impl AsRef<[u8]>,
-        example: bool,
+        found: FoundScript,
+        contents: &[u8],
         modified: SystemTime,
     ) -> anyhow::Result<Script> {
-        let mut vm = Lua::new();
-        let bytecode = self.compiler.compile(src.as_ref());
-
         let mut script = Script {
-            path,
-            is_example: example,
-            bytecode,
+            id: ScriptId::new(&found.path),
+            path: found.path,
+            category: found.category,
+            game_dir: found.game_dir,
+            is_example: found.example,
+            bytecode: self.compiler.compile(contents),
+            hash: source_hash(contents),
             modified,
-            vm,
+            vm: new_vm(&self.ctx, self.api.as_ref(), &self.watchdog, self.max_memory)?,
+            api: self.api.clone(),
+            watchdog: self.watchdog.clone(),
+            max_memory: self.max_memory,
+            ctx: self.ctx.clone(),
         };
-
-        // Load the bytecode
         script.populate_scope();
+
         // Run Once! (load globals, etc)
-        script.vm.load(&script.bytecode).exec()?;
+        self.ctx.set_running_script(&script);
+        self.watchdog.start();
+        script
+            .run()
+            .map_err(|e| engine_error(&script.path, e))?;
 
         Ok(script)
     }
+
+    /// `missing` holds the scripts whose file is gone, a new file with the same contents is
+    /// the same script renamed.
+    fn load_found_script(
+        &mut self,
+        console: &mut Console,
+        missing: &mut Vec<Script>,
+        found: FoundScript,
+    ) -> anyhow::Result<()> {
+        let path = found.path.clone();
+        let modified = path
+            .metadata()
+            .and_then(|m| m.modified())
+            .map_err(|e| EngineError::io(&path, e))?;
+
+        if let Some(existing) = self.scripts.iter_mut().find(|s| s.path == path) {
+            if existing.modified == modified {
+                return Ok(());
+            }
+            existing.modified = modified;
+
+            let contents = fs::read(&path).map_err(|e| EngineError::io(&path, e))?;
+            existing.hash = source_hash(&contents);
+            let bytecode = self.compiler.compile(&contents);
+
+            console.log(format!("Reloading changed script {path:?}"));
+            self.loaded.push(existing.id.clone());
+            self.ctx.set_running_script(&*existing);
+            self.watchdog.start();
+            existing.hot_swap(bytecode).map_err(|e| engine_error(&path, e))?;
+            return Ok(());
+        }
+
+        let contents = fs::read(&path).map_err(|e| EngineError::io(&path, e))?;
+
+        // Move renamed script
+        let hash = source_hash(&contents);
+        if let Some(i) = missing.iter().position(|s| s.hash == hash) {
+            let mut script = missing.swap_remove(i);
+            console.log(format!("Renamed script {:?} to {path:?}", script.path));
+
+            let old = mem::replace(&mut script.id, ScriptId::new(&path));
+            self.ctx.assets_mut().rename_owner(&old, &script.id);
+            self.renamed.push((old, script.id.clone()));
+            script.path = path;
+            script.modified = modified;
+            script.is_example = found.example;
+            script.category = found.category;
+            script.game_dir = found.game_dir;
+            self.scripts.push(script);
+            return Ok(());
+        }
+
+        console.log(format!("Adding new script {path:?}"));
+        let script = self.compile_and_init_script(found, &contents, modified)?;
+        self.loaded.push(script.id.clone());
+        self.scripts.push(script);
+        Ok(())
+    }
 }
 
 pub struct Script {
+    id: ScriptId,
     path: PathBuf,
+    category: String,
+    game_dir: Option<PathBuf>,
     bytecode: Vec<u8>,
-    // TODO: store chunks in script for perf
-    // chunk: Chunk<'a, 'a>,
+    hash: u64,
     vm: Lua,
     is_example: bool,
     modified: SystemTime,
+    // Needed to create a new VM on reset
+    api: Option<Rc<HostApi>>,
+    watchdog: Rc<Watchdog>,
+    max_memory: usize,
+    ctx: Context,
+}
+
+impl Script {
+    /// Run the top level code.
+    fn run(&self) -> mlua::Result<()> {
+        self.vm
+            .load(self.bytecode.as_slice())
+            .set_name(format!("={}", chunk_name(&self.path)))
+            .set_mode(ChunkMode::Binary)
+            .exec()
+    }
+
+    /// Run new code in the same VM, keeping the values of globals whose type did not change.
+    /// The globals are restored if running the new top level code fails.
+    fn hot_swap(&mut self, bytecode: Vec<u8>) -> mlua::Result<()> {
+        let old_bytecode = mem::replace(&mut self.bytecode, bytecode);
+
+        let globals = self.vm.globals();
+        let old: Vec<(Value, Value)> = globals
+            .clone()
+            .pairs::<Value, Value>()
+            .collect::<mlua::Result<_>>()?;
+
+        if let Err(e) = self.run() {
+            for (name, value) in old {
+                globals.raw_set(name, value)?;
+            }
+            self.bytecode = old_bytecode;
+            return Err(e);
+        }
+
+        for (name, value) in old {
+            // Functions come from the new code
+            if matches!(value, Value::Function(_)) {
+                continue;
+            }
+            let new: Value = globals.raw_get(name.clone())?;
+            if new.type_name() == value.type_name() {
+                globals.raw_set(name, value)?;
+            }
+        }
+        Ok(())
+    }
 }
 
 impl GameScript for Script {
+    fn id<'a>(&'a self) -> &'a ScriptId {
+        &self.id
+    }
     fn path<'a>(&'a self) -> &'a Path {
         &self.path
     }
     fn name<'a>(&'a self) -> Option<&'a str> {
-        match self.path.file_name() {
+        let path = self.game_dir.as_deref().unwrap_or(&self.path);
+        match path.file_name() {
             Some(path) => path.to_str(),
             _ => None,
         }
     }
+    fn category<'a>(&'a self) -> &'a str {
+        &self.category
+    }
+    fn game_dir<'a>(&'a self) -> Option<&'a Path> {
+        self.game_dir.as_deref()
+    }
     fn is_example(&self) -> bool {
         self.is_example
     }
-    fn reset(&mut self) {
-        // TODO
-        self.vm.globals().clear();
+    fn reset(&mut self) -> anyhow::Result<()> {
+        // A new VM, globals can not be told apart from the standard library otherwise
+        self.vm = new_vm(&self.ctx, self.api.as_ref(), &self.watchdog, self.max_memory)
+            .map_err(|e| anyhow::Error::new(e).context("Failed to create a Lua VM"))?;
         self.populate_scope();
+        Ok(())
     }
     fn populate_scope(&mut self) {
-        let lua = &mut self.vm;
+        let lua = &self.vm;
         let globals = lua.globals();
 
-        let table_color = |Color { r, g, b, a }: Color| {
-            lua.create_table_from([("r", r), ("g", g), ("b", b), ("a", a)])
-                .and_then(|t| {
-                    t.set_readonly(true);
-                    Ok(t)
-                })
-        };
-        let table_s = readonly_table!(lua, Vec2 { x, y });
-        let table_vec2 = |Vec2 { x, y }: Vec2| {
-            lua.create_table_from([("x", x), ("y", y)]).and_then(|t| {
-                t.set_readonly(true);
-                Ok(t)
-            })
-        };
-        let table_vec3 = |Vec3 { x, y, z }: Vec3| {
-            lua.create_table_from([("x", x), ("y", y), ("z", z)])
-                .and_then(|t| {
-                    t.set_readonly(true);
-                    Ok(t)
-                })
-        };
-        let table_rect = |Rect { x, y, w, h }: Rect| {
-            lua.create_table_from([("x", x), ("y", y), ("w", w), ("h", h)])
-                .and_then(|t| {
-                    t.set_readonly(true);
-                    Ok(t)
-                })
-        };
-
-        for (name, color) in COLORS {
-            globals.set(name, table_color(color).unwrap()).unwrap();
+        // NOTE: only fails when out of memory
+        for (name, Color { r, g, b, a }) in COLORS {
+            let color = readonly(lua.create_table_from([("r", r), ("g", g), ("b", b), ("a", a)]));
+            let _ = color.and_then(|c| globals.set(name, c));
+        }
+        for (name, key) in ffi::KEYS {
+            let _ = globals.set(name, key as u16);
         }
-        for (key, code) in ffi::KEYS {
-            globals.set(key, code as u16);
+        for (name, button) in ffi::MOUSE_BUTTONS {
+            let _ = globals.set(name, button as u8);
         }
-        for (name, mb) in ffi::MOUSE_BUTTONS {
-            globals.set(name, mb as u8);
+        for (name, _, value) in self.api.iter().flat_map(|api| api.constants()) {
+            let _ = from_value(lua, value.clone()).and_then(|v| globals.set(name.as_str(), v));
         }
     }
 }
@@ -146,103 +496,213 @@ impl GameScript for Script {
 impl ScriptEngine for Engine {
     type Script = Script;
 
-    fn extension<'a>() -> &'a str {
-        "lua"
+    fn extensions() -> &'static [&'static str] {
+        &["lua"]
     }
-    fn expose_layer(&mut self) {
-        // TODO
+    fn context(&self) -> &Context {
+        &self.ctx
+    }
+    fn expose_api(&mut self, api: &HostApi) {
+        // Every script has its own VM, the API is registered when it is created
+        self.api = Some(Rc::new(api.clone()));
+    }
+    fn set_limits(&mut self, limits: &Limits) {
+        // NOTE: Luau has its own stack limits, and no limits on string or table sizes
+        self.watchdog
+            .set_budget(Duration::from_millis(limits.frame_budget_ms));
+        self.watchdog.set_max_operations(limits.max_operations);
+        self.max_memory = limits.max_memory;
+        for script in &mut self.scripts {
+            script.max_memory = limits.max_memory;
+            // NOTE: only fails for VMs whose memory is not managed by mlua
+            let _ = script.vm.set_memory_limit(limits.max_memory);
+        }
     }
     fn write_examples(&mut self, warnings: &mut Vec<String>) -> Result<(), Vec<std::io::Error>> {
-        // TODO
-        Ok(())
+        write_examples(self.ctx.dirs(), Self::extension(), warnings)
     }
-    fn scripts<'a>(&'a mut self) -> &'a mut [Self::Script] {
-        &mut self.scripts
+    fn scripts<'a>(&'a mut self) -> Vec<&'a mut Self::Script> {
+        self.scripts.iter_mut().collect()
     }
     fn load_scripts(
         &mut self,
-        console: &mut crate::menu::Console,
+        console: &mut Console,
         errors: &mut ErrorMap,
         from: &[ScriptDir],
     ) -> anyhow::Result<()> {
-        let files = read_files_from_dir(
-            &[&dirs().scripts, &dirs().examples],
-            lua_engine::Engine::extension(),
-            console,
-            errors,
-        )?;
-
-        // TODO: add_error()
+        let mut result = Ok(());
 
-        for (entry, contents, example) in files {
-            let path = entry.path();
+        for source in from {
+            console.log(format!("==> Loading scripts from {:?}", source.path(self.ctx.dirs())));
+        }
+        let first_error = errors.len();
+        let found = find_scripts(self.ctx.dirs(), from, Self::extensions(), errors);
+        for (path, e) in &errors[first_error..] {
+            console.err(format!("Failed to read dir {path:?}: {}", e.message()));
+            result = Err(anyhow::anyhow!("Failed to read script folders"));
+        }
 
-            // Extract metadata
-            let metadata = if let Ok(m) = entry.metadata() {
-                m
+        // Scripts that are not found anymore were either renamed or deleted
+        let (mut missing, scripts) = mem::take(&mut self.scripts).into_iter().partition(|s: &Script| {
+            let dir = if s.is_example {
+                ScriptDir::Examples
             } else {
-                console.err(format!(
-                    "Failed to read metadata of file '{entry:?}', skipping"
-                ));
-                continue;
+                ScriptDir::Scripts
             };
+            let searched = from.contains(&dir) || !s.path.is_file();
+            searched && !found.iter().any(|f| f.path == s.path)
+        });
+        self.scripts = scripts;
+        self.renamed.clear();
+        self.loaded.clear();
 
-            let modified = if let Ok(m) = metadata.modified() {
-                m
-            } else {
-                console.err(format!(
-                    "Failed to read modification time of file '{entry:?}', skipping"
-                ));
-                continue;
-            };
+        // One broken script does not keep the others from loading
+        for found in found {
+            let path = found.path.clone();
+            if let Err(e) = self.load_found_script(console, &mut missing, found) {
+                errors.push((path, e.into()));
+                result = Err(anyhow::anyhow!("Failed to load scripts"));
+            }
+        }
 
-            // Check for existing
-            let existing_idx = self
-                .scripts
-                .iter()
-                .enumerate()
-                .find(|(_, s)| s.path == path && s.modified != modified)
-                .and_then(|(i, _)| Some(i));
-
-            // Update the script
-            if let Some(idx) = existing_idx {
-                // HACK: do not store the script, to avoid mutably borrowing self
-                console.log(format!("Updating existing script {path:?}"));
-                let prev_time = self.scripts[idx].modified;
-                self.scripts[idx] =
-                    self.compile_and_init_script(path.clone(), contents, true, prev_time)?;
-                continue;
+        for script in missing {
+            console.log(format!("Removing deleted script {:?}", script.path));
+            self.ctx.assets_mut().release(&script.id);
+        }
+
+        result
+    }
+    fn call_function(&mut self, id: &ScriptId, name: impl AsRef<str>) -> anyhow::Result<()> {
+        let name = name.as_ref();
+        let script = find_script(&mut self.scripts, id)?;
+        self.ctx.set_running_script(&*script);
+
+        let func: Option<Function> = script
+            .vm
+            .globals()
+            .get(name)
+            .map_err(|e| EngineError::Runtime(lua_error(&script.path, e)))?;
+        let Some(func) = func else {
+            return Err(EngineError::MissingHook {
+                path: script.path.clone(),
+                hook: name.to_owned(),
             }
+            .into());
+        };
 
-            // New script
-            console.log(format!("Adding new script {path:?}"));
-            let now = SystemTime::now();
+        self.watchdog.start();
+        func.call::<_, ()>(()) // IMPORTANT: no args are passed
+            .map_err(|e| EngineError::Runtime(lua_error(&script.path, e)).into())
+    }
+    fn has_function(&mut self, id: &ScriptId, name: &str) -> bool {
+        find_script(&mut self.scripts, id)
+            .is_ok_and(|script| script.vm.globals().get::<_, Function>(name).is_ok())
+    }
+    fn restart_script(&mut self, id: &ScriptId) -> anyhow::Result<()> {
+        let script = find_script(&mut self.scripts, id)?;
+        script.reset()?;
+        self.ctx.set_running_script(&*script);
 
-            // TODO: handle
-            let script = self.compile_and_init_script(path.clone(), contents, example, now)?;
+        self.watchdog.start();
+        script.run().map_err(|e| engine_error(&script.path, e).into())
+    }
+    fn renamed_scripts(&self) -> &[(ScriptId, ScriptId)] {
+        &self.renamed
+    }
+    fn loaded_scripts(&self) -> &[ScriptId] {
+        &self.loaded
+    }
+    fn check_script(&self, path: &Path, source: &[u8]) -> Vec<Diagnostic> {
+        let lua = match new_vm(&self.ctx, self.api.as_ref(), &self.watchdog, self.max_memory) {
+            Ok(lua) => lua,
+            Err(e) => return vec![Diagnostic::error(path, e)],
+        };
+        let result = lua
+            .load(source)
+            .set_name(format!("={}", chunk_name(path)))
+            .set_mode(ChunkMode::Text)
+            .into_function();
+        if let Err(e) = result {
+            let e = lua_error(path, e);
+            return vec![Diagnostic::error(path, e.message).at(e.line, e.column)];
+        }
+        let source = match std::str::from_utf8(source) {
+            Ok(source) => source,
+            Err(e) => return vec![Diagnostic::error(path, e)],
+        };
 
-            self.scripts.push(script);
+        // NOTE: without running the script, hooks are its global functions without parameters
+        let tokens = lua_check::lua_tokens(source);
+        let (defined, hooks) = lua_check::lua_definitions(&tokens);
+        let mut diagnostics = check::missing_hooks(path, hooks.iter().copied());
+
+        // Calls to names that are neither globals of the VM, like the API and the standard library,
+        // nor defined by the script.
+        let globals: HashSet<String> = lua
+            .globals()
+            .pairs::<String, Value>()
+            .filter_map(|pair| pair.ok().map(|(name, _)| name))
+            .collect();
+        for (name, line, column) in lua_check::unknown_calls(&tokens, &defined, &globals) {
+            diagnostics.push(
+                Diagnostic::warning(path, format!("call to unknown function `{name}`")).at(line, column),
+            );
         }
 
-        // TODO
-        Ok(())
+        diagnostics
+    }
+    fn api_reference(&self) -> ApiReference {
+        ApiReference::new("lua", self.api.as_deref().unwrap_or(&HostApi::new()))
     }
-    fn call_function(&mut self, script_index: usize, name: impl AsRef<str>) -> anyhow::Result<()> {
-        // NOTE: Possible OOB
-        let script = &mut self.scripts[script_index];
-        let globals = script.vm.globals();
-        let func: Function = globals.get(name.as_ref())?;
-        // Call the function
-        func.call::<_, ()>(())?;
+}
 
-        Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::TempDir;
+
+    /// Load `source` as the only script, with `limits` and no time budget.
+    fn run(name: &str, source: &str, limits: Limits) -> anyhow::Result<()> {
+        let tmp = TempDir::new(name);
+        let mut engine = Engine::new(Context::new(&tmp.0));
+        engine.set_limits(&Limits {
+            frame_budget_ms: 0,
+            ..limits
+        });
+        let dirs = engine.context().dirs().clone();
+        dirs.create()?;
+        let path = dirs.scripts.join("limits.lua");
+        fs::write(&path, source)?;
+
+        let mut errors = vec![];
+        if engine.load_scripts(&mut Console::new(), &mut errors, &[ScriptDir::Scripts]).is_err() {
+            let (_, e) = errors.remove(0);
+            return Err(e.into());
+        }
+        engine.call_function(&ScriptId::new(&path), "update")
     }
-    fn reload_scripts(
-        &mut self,
-        console: &mut crate::menu::Console,
-        errors: &mut ErrorMap,
-    ) -> anyhow::Result<()> {
-        // TODO
-        Ok(())
+
+    #[test]
+    fn operations_are_limited() {
+        let limits = Limits {
+            max_operations: 1000,
+            ..Limits::default()
+        };
+        let err = run("lua-operations", "function update() while true do end end", limits).unwrap_err();
+        assert!(format!("{err:#}").contains("Aborted after 1000 operations"), "{err:#}");
+
+        let source = "function update() for i = 1, 100 do end end";
+        assert!(run("lua-operations-ok", source, Limits::default()).is_ok());
+    }
+
+    #[test]
+    fn memory_is_limited() {
+        let limits = Limits {
+            max_memory: 1024 * 1024,
+            ..Limits::default()
+        };
+        let source = "t = {}\nfunction update() for i = 1, 1000000 do t[i] = tostring(i) end end";
+        let err = run("lua-memory", source, limits).unwrap_err();
+        assert!(format!("{err:#}").contains("memory"), "{err:#}");
     }
 }
@@ -0,0 +1,297 @@
+//! Runs the scripts of every enabled backend side by side.
+//!
+//! Each backend keeps its own scripts, calls are routed by the extension of the script file.
+
+use std::ffi::OsStr;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::*;
+
+/// The parts of [`ScriptEngine`] routed to a backend, object safe so the backends can be stored together.
+trait Backend {
+    fn extensions(&self) -> &'static [&'static str];
+    fn expose_api(&mut self, api: &HostApi);
+    fn set_limits(&mut self, limits: &Limits);
+    fn write_examples(&mut self, warnings: &mut Vec<String>) -> Result<(), Vec<io::Error>>;
+    fn scripts(&mut self) -> Vec<&mut (dyn GameScript + 'static)>;
+    fn load_scripts(
+        &mut self,
+        console: &mut Console,
+        errors: &mut ErrorMap,
+        from: &[ScriptDir],
+    ) -> anyhow::Result<()>;
+    fn call_function(&mut self, id: &ScriptId, name: &str) -> anyhow::Result<()>;
+    fn has_function(&mut self, id: &ScriptId, name: &str) -> bool;
+    fn restart_script(&mut self, id: &ScriptId) -> anyhow::Result<()>;
+    fn stop_script(&mut self, id: &ScriptId) -> anyhow::Result<()>;
+    fn check_script(&self, path: &Path, source: &[u8]) -> Vec<Diagnostic>;
+    fn api_reference(&self) -> ApiReference;
+    fn definitions(&self) -> Option<(&'static str, String)>;
+    fn renamed_scripts(&self) -> &[(ScriptId, ScriptId)];
+    fn loaded_scripts(&self) -> &[ScriptId];
+}
+
+impl<E> Backend for E
+where
+    E: ScriptEngine,
+    E::Script: Sized + 'static,
+{
+    fn extensions(&self) -> &'static [&'static str] {
+        E::extensions()
+    }
+    fn expose_api(&mut self, api: &HostApi) {
+        ScriptEngine::expose_api(self, api)
+    }
+    fn set_limits(&mut self, limits: &Limits) {
+        ScriptEngine::set_limits(self, limits)
+    }
+    fn write_examples(&mut self, warnings: &mut Vec<String>) -> Result<(), Vec<io::Error>> {
+        ScriptEngine::write_examples(self, warnings)
+    }
+    fn scripts(&mut self) -> Vec<&mut (dyn GameScript + 'static)> {
+        ScriptEngine::scripts(self)
+            .into_iter()
+            .map(|s| s as &mut dyn GameScript)
+            .collect()
+    }
+    fn load_scripts(
+        &mut self,
+        console: &mut Console,
+        errors: &mut ErrorMap,
+        from: &[ScriptDir],
+    ) -> anyhow::Result<()> {
+        ScriptEngine::load_scripts(self, console, errors, from)
+    }
+    fn call_function(&mut self, id: &ScriptId, name: &str) -> anyhow::Result<()> {
+        ScriptEngine::call_function(self, id, name)
+    }
+    fn has_function(&mut self, id: &ScriptId, name: &str) -> bool {
+        ScriptEngine::has_function(self, id, name)
+    }
+    fn restart_script(&mut self, id: &ScriptId) -> anyhow::Result<()> {
+        ScriptEngine::restart_script(self, id)
+    }
+    fn stop_script(&mut self, id: &ScriptId) -> anyhow::Result<()> {
+        ScriptEngine::stop_script(self, id)
+    }
+    fn check_script(&self, path: &Path, source: &[u8]) -> Vec<Diagnostic> {
+        ScriptEngine::check_script(self, path, source)
+    }
+    fn api_reference(&self) -> ApiReference {
+        ScriptEngine::api_reference(self)
+    }
+    fn definitions(&self) -> Option<(&'static str, String)> {
+        ScriptEngine::definitions(self)
+    }
+    fn renamed_scripts(&self) -> &[(ScriptId, ScriptId)] {
+        ScriptEngine::renamed_scripts(self)
+    }
+    fn loaded_scripts(&self) -> &[ScriptId] {
+        ScriptEngine::loaded_scripts(self)
+    }
+}
+
+const EXTENSIONS: &[&str] = match (IS_RHAI, IS_LUA, IS_WASM) {
+    (true, true, true) => &["rhai", "lua", "wasm"],
+    (true, true, false) => &["rhai", "lua"],
+    (true, false, true) => &["rhai", "wasm"],
+    (true, false, false) => &["rhai"],
+    (false, true, true) => &["lua", "wasm"],
+    (false, true, false) => &["lua"],
+    _ => &["wasm"],
+};
+
+pub struct Engine {
+    /// In the order of [`EXTENSIONS`]
+    backends: Vec<Box<dyn Backend>>,
+    renamed: Vec<(ScriptId, ScriptId)>,
+    loaded: Vec<ScriptId>,
+    /// Shared by every backend
+    ctx: Context,
+}
+
+impl Engine {
+    pub fn new(ctx: Context) -> Self {
+        let mut backends: Vec<Box<dyn Backend>> = vec![];
+        #[cfg(feature = "rhai-engine")]
+        backends.push(Box::new(rhai_engine::Engine::new(ctx.clone())));
+        #[cfg(feature = "lua-engine")]
+        backends.push(Box::new(lua_engine::Engine::new(ctx.clone())));
+        #[cfg(feature = "wasm-engine")]
+        backends.push(Box::new(wasm_engine::Engine::new(ctx.clone())));
+
+        Self {
+            backends,
+            renamed: vec![],
+            loaded: vec![],
+            ctx,
+        }
+    }
+
+    /// The backend running scripts with the extension of `path`.
+    fn backend(&mut self, path: &Path) -> anyhow::Result<&mut dyn Backend> {
+        let ext = path.extension().and_then(OsStr::to_str).unwrap_or_default();
+        self.backends
+            .iter_mut()
+            .find(|b| b.extensions().contains(&ext))
+            .map(|b| &mut **b as &mut dyn Backend)
+            .ok_or_else(|| anyhow::anyhow!("No scripting backend for {path:?}"))
+    }
+
+    /// Load the scripts of the backends `reload` picks, the renamed and loaded scripts are theirs.
+    fn load_backends(
+        &mut self,
+        console: &mut Console,
+        errors: &mut ErrorMap,
+        from: &[ScriptDir],
+        reload: impl Fn(&dyn Backend) -> bool,
+    ) -> anyhow::Result<()> {
+        let mut result = Ok(());
+        self.renamed.clear();
+        self.loaded.clear();
+
+        // A failing backend does not keep the others from loading
+        for backend in self.backends.iter_mut().filter(|b| reload(&***b)) {
+            if let Err(e) = backend.load_scripts(console, errors, from) {
+                result = Err(e);
+            }
+            self.renamed.extend_from_slice(backend.renamed_scripts());
+            self.loaded.extend_from_slice(backend.loaded_scripts());
+        }
+        result
+    }
+}
+
+impl ScriptEngine for Engine {
+    type Script = dyn GameScript;
+
+    fn extensions() -> &'static [&'static str] {
+        EXTENSIONS
+    }
+    fn context(&self) -> &Context {
+        &self.ctx
+    }
+    fn expose_api(&mut self, api: &HostApi) {
+        for backend in &mut self.backends {
+            backend.expose_api(api);
+        }
+    }
+    fn set_limits(&mut self, limits: &Limits) {
+        for backend in &mut self.backends {
+            backend.set_limits(limits);
+        }
+    }
+    fn write_examples(&mut self, warnings: &mut Vec<String>) -> Result<(), Vec<io::Error>> {
+        let mut errors = vec![];
+        for backend in &mut self.backends {
+            if let Err(e) = backend.write_examples(warnings) {
+                errors.extend(e);
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+    fn scripts<'a>(&'a mut self) -> Vec<&'a mut Self::Script> {
+        self.backends.iter_mut().flat_map(|b| b.scripts()).collect()
+    }
+    fn load_scripts(
+        &mut self,
+        console: &mut Console,
+        errors: &mut ErrorMap,
+        from: &[ScriptDir],
+    ) -> anyhow::Result<()> {
+        self.load_backends(console, errors, from, |_| true)
+    }
+    fn call_function(&mut self, id: &ScriptId, name: impl AsRef<str>) -> anyhow::Result<()> {
+        self.backend(id.path())?.call_function(id, name.as_ref())
+    }
+    fn has_function(&mut self, id: &ScriptId, name: &str) -> bool {
+        self.backend(id.path())
+            .is_ok_and(|backend| backend.has_function(id, name))
+    }
+    fn restart_script(&mut self, id: &ScriptId) -> anyhow::Result<()> {
+        self.backend(id.path())?.restart_script(id)
+    }
+    fn stop_script(&mut self, id: &ScriptId) -> anyhow::Result<()> {
+        self.backend(id.path())?.stop_script(id)
+    }
+    fn check_script(&self, path: &Path, source: &[u8]) -> Vec<Diagnostic> {
+        let ext = path.extension().and_then(OsStr::to_str).unwrap_or_default();
+        match self.backends.iter().find(|b| b.extensions().contains(&ext)) {
+            Some(backend) => backend.check_script(path, source),
+            None => vec![Diagnostic::error(path, "No scripting backend for this file")],
+        }
+    }
+    fn api_reference(&self) -> ApiReference {
+        // NOTE: every backend registers the same API
+        self.backends[0].api_reference()
+    }
+    fn definitions(&self) -> Option<(&'static str, String)> {
+        self.backends.iter().find_map(|b| b.definitions())
+    }
+    fn reload_changed(
+        &mut self,
+        console: &mut Console,
+        errors: &mut ErrorMap,
+        changed: &[PathBuf],
+    ) -> anyhow::Result<()> {
+        // The scripts of the other backends did not change
+        let from = [ScriptDir::Examples, ScriptDir::Scripts];
+        self.load_backends(console, errors, &from, |backend| {
+            changed.iter().any(|path| {
+                let ext = path.extension().and_then(OsStr::to_str).unwrap_or_default();
+                backend.extensions().contains(&ext)
+            })
+        })
+    }
+    fn renamed_scripts(&self) -> &[(ScriptId, ScriptId)] {
+        &self.renamed
+    }
+    fn loaded_scripts(&self) -> &[ScriptId] {
+        &self.loaded
+    }
+}
+
+#[cfg(all(test, feature = "rhai-engine", feature = "lua-engine"))]
+mod tests {
+    use std::fs;
+    use std::time::{Duration, SystemTime};
+
+    use super::*;
+    use crate::common::TempDir;
+
+    #[test]
+    fn reload_changed_only_runs_changed_scripts() {
+        let tmp = TempDir::new("reload-changed");
+        let mut engine = Engine::new(Context::new(&tmp.0));
+        engine.expose_layer();
+        let dirs = engine.context().dirs().clone();
+        dirs.create().unwrap();
+        let (rhai, lua) = (dirs.scripts.join("a.rhai"), dirs.scripts.join("b.lua"));
+        fs::write(&rhai, "let x = 1;").unwrap();
+        fs::write(&lua, "x = 1").unwrap();
+
+        let (mut console, mut errors) = (Console::new(), vec![]);
+        engine.load_scripts(&mut console, &mut errors, &[ScriptDir::Scripts]).unwrap();
+        let mut loaded = engine.loaded_scripts().to_vec();
+        loaded.sort_by(|a, b| a.path().cmp(b.path()));
+        assert_eq!(loaded, [ScriptId::new(&rhai), ScriptId::new(&lua)]);
+
+        let changed = [lua.clone()];
+        fs::write(&lua, "x = 2").unwrap();
+        let later = SystemTime::now() + Duration::from_secs(10);
+        fs::File::options().write(true).open(&lua).unwrap().set_modified(later).unwrap();
+        engine.reload_changed(&mut console, &mut errors, &changed).unwrap();
+        assert_eq!(engine.loaded_scripts(), [ScriptId::new(&lua)]);
+
+        // Nothing changed
+        engine.reload_changed(&mut console, &mut errors, &changed).unwrap();
+        assert!(engine.loaded_scripts().is_empty());
+        assert!(errors.is_empty());
+    }
+}
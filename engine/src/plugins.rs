@@ -0,0 +1,515 @@
+//! Native plugins, dynamic libraries in the `plugins` folder adding functions and constants to the [`HostApi`].
+//!
+//! A plugin exports two C functions:
+//! - `kgames_plugin_abi() -> u32`, the [`PLUGIN_ABI`] it was built for. Plugins built for another version are not loaded.
+//! - `kgames_plugin_init(registrar: *mut PluginRegistrar) -> bool`, registering its functions and constants.
+//!   Nothing is registered if it returns `false`.
+//!
+//! Values are [`PluginValue`]s tagged with one of the `TYPE_*` constants, strings are UTF-8 and not nul terminated.
+//! Strings given to a plugin are only valid during the call, the ones it returns until its next call.
+
+use std::any::Any;
+use std::ffi::{c_char, c_void, CStr, OsStr};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::{env, fs, io, ptr, slice};
+
+use libloading::Library;
+
+use crate::bindings::{Type, Value};
+use crate::host::{HostApi, MAX_PARAMS};
+use crate::{Context, Dirs, EngineError, ErrorMap};
+
+/// Version of the interface below, changed whenever plugins have to be rebuilt.
+pub const PLUGIN_ABI: u32 = 1;
+
+pub const TYPE_UNIT: u32 = 0;
+pub const TYPE_FLOAT: u32 = 1;
+pub const TYPE_INT: u32 = 2;
+pub const TYPE_BOOL: u32 = 3;
+pub const TYPE_STRING: u32 = 4;
+
+/// A value given to or returned by a plugin.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct PluginValue {
+    /// One of the `TYPE_*` constants
+    pub ty: u32,
+    /// For `TYPE_INT`, and `TYPE_BOOL` where `0` is false
+    pub int: i64,
+    /// For `TYPE_FLOAT`
+    pub float: f64,
+    /// For `TYPE_STRING`, `len` bytes
+    pub string: *const u8,
+    pub len: usize,
+}
+
+impl PluginValue {
+    const UNIT: Self = Self {
+        ty: TYPE_UNIT,
+        int: 0,
+        float: 0.0,
+        string: ptr::null(),
+        len: 0,
+    };
+}
+
+/// A function of a plugin, `data` is the pointer it was registered with.
+/// It writes its result to `result` and returns `true`, or writes an error message as a string and returns `false`.
+pub type PluginFunction = unsafe extern "C" fn(
+    data: *mut c_void,
+    args: *const PluginValue,
+    count: usize,
+    result: *mut PluginValue,
+) -> bool;
+
+/// Given to `kgames_plugin_init`, only valid during the call.
+/// Both functions return `false` if a name is not UTF-8 or a type is unknown,
+/// and `register_function` if there are more than [`MAX_PARAMS`] parameters.
+#[repr(C)]
+pub struct PluginRegistrar {
+    pub abi: u32,
+    /// Register a function taking `count` parameters of the types in `params`, `returns` is `TYPE_UNIT` for nothing.
+    pub register_function: unsafe extern "C" fn(
+        registrar: *mut PluginRegistrar,
+        name: *const c_char,
+        params: *const u32,
+        count: usize,
+        returns: u32,
+        func: PluginFunction,
+        data: *mut c_void,
+    ) -> bool,
+    /// Register a global constant.
+    pub register_constant: unsafe extern "C" fn(
+        registrar: *mut PluginRegistrar,
+        name: *const c_char,
+        value: PluginValue,
+    ) -> bool,
+    registry: *mut Registry,
+}
+
+impl PluginRegistrar {
+    fn new(registry: &mut Registry) -> Self {
+        Self {
+            abi: PLUGIN_ABI,
+            register_function,
+            register_constant,
+            registry,
+        }
+    }
+}
+
+struct PluginFn {
+    name: String,
+    params: Vec<Type>,
+    returns: u32,
+    func: PluginFunction,
+    data: *mut c_void,
+}
+
+/// What a plugin registered, added to the API once its init succeeded.
+#[derive(Default)]
+struct Registry {
+    functions: Vec<PluginFn>,
+    constants: Vec<(String, Type, Value)>,
+    /// Why the last registration failed
+    error: Option<String>,
+}
+
+fn plugin_type(ty: u32) -> Option<Type> {
+    match ty {
+        TYPE_FLOAT => Some(Type::Float),
+        TYPE_INT => Some(Type::Int),
+        TYPE_BOOL => Some(Type::Bool),
+        TYPE_STRING => Some(Type::String),
+        _ => None,
+    }
+}
+
+/// Read a value of a plugin, strings are copied.
+///
+/// # Safety
+/// A string must point to `len` readable bytes.
+unsafe fn read_value(value: &PluginValue) -> Option<Value> {
+    let value = match value.ty {
+        TYPE_UNIT => Value::Unit,
+        TYPE_FLOAT => Value::Float(value.float as f32),
+        TYPE_INT => Value::Int(value.int),
+        TYPE_BOOL => Value::Bool(value.int != 0),
+        TYPE_STRING if value.string.is_null() => return None,
+        TYPE_STRING => {
+            let bytes = slice::from_raw_parts(value.string, value.len);
+            Value::String(String::from_utf8_lossy(bytes).into_owned())
+        }
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// A value for a plugin, strings borrow from `value`.
+fn plugin_value(value: &Value) -> PluginValue {
+    let mut v = PluginValue::UNIT;
+    match *value {
+        Value::Float(f) => (v.ty, v.float) = (TYPE_FLOAT, f.into()),
+        Value::Int(i) => (v.ty, v.int) = (TYPE_INT, i),
+        Value::Bool(b) => (v.ty, v.int) = (TYPE_BOOL, b.into()),
+        Value::String(ref s) => (v.ty, v.string, v.len) = (TYPE_STRING, s.as_ptr(), s.len()),
+        // Plugins can not declare parameters of other types
+        _ => {}
+    }
+    v
+}
+
+/// # Safety
+/// `name` is nul terminated.
+unsafe fn read_name(registry: &mut Registry, name: *const c_char) -> Option<String> {
+    let name = (!name.is_null())
+        .then(|| CStr::from_ptr(name).to_str().ok())
+        .flatten();
+    if name.is_none() {
+        registry.error = Some("a name is null or not valid UTF-8".to_owned());
+    }
+    name.map(str::to_owned)
+}
+
+unsafe extern "C" fn register_function(
+    registrar: *mut PluginRegistrar,
+    name: *const c_char,
+    params: *const u32,
+    count: usize,
+    returns: u32,
+    func: PluginFunction,
+    data: *mut c_void,
+) -> bool {
+    let registry = &mut *(*registrar).registry;
+    let Some(name) = read_name(registry, name) else {
+        return false;
+    };
+    if count > MAX_PARAMS {
+        registry.error = Some(format!(
+            "{name}() has {count} parameters, functions can have at most {MAX_PARAMS}"
+        ));
+        return false;
+    }
+
+    let params = match count {
+        0 => &[],
+        _ => slice::from_raw_parts(params, count),
+    };
+    let types: Option<Vec<_>> = params.iter().map(|ty| plugin_type(*ty)).collect();
+    match types {
+        Some(params) if returns == TYPE_UNIT || plugin_type(returns).is_some() => {
+            registry.functions.push(PluginFn {
+                name,
+                params,
+                returns,
+                func,
+                data,
+            });
+            true
+        }
+        _ => {
+            registry.error = Some(format!("{name}() has a parameter or result of unknown type"));
+            false
+        }
+    }
+}
+
+unsafe extern "C" fn register_constant(
+    registrar: *mut PluginRegistrar,
+    name: *const c_char,
+    value: PluginValue,
+) -> bool {
+    let registry = &mut *(*registrar).registry;
+    let Some(name) = read_name(registry, name) else {
+        return false;
+    };
+
+    match (plugin_type(value.ty), read_value(&value)) {
+        (Some(ty), Some(value)) => {
+            registry.constants.push((name, ty, value));
+            true
+        }
+        _ => {
+            registry.error = Some(format!("{name} has an unknown type"));
+            false
+        }
+    }
+}
+
+/// Load a plugin and add what it registers to `api`.
+fn load_plugin(api: &mut HostApi, path: &Path) -> anyhow::Result<()> {
+    // SAFETY: plugins are trusted like scripts, running their code is the point
+    let library = unsafe { Library::new(path)? };
+
+    let abi = unsafe { *library.get::<unsafe extern "C" fn() -> u32>(b"kgames_plugin_abi\0")? };
+    let version = unsafe { abi() };
+    if version != PLUGIN_ABI {
+        anyhow::bail!("built for plugin ABI version {version}, kgames supports version {PLUGIN_ABI}");
+    }
+
+    let init = unsafe {
+        *library.get::<unsafe extern "C" fn(*mut PluginRegistrar) -> bool>(b"kgames_plugin_init\0")?
+    };
+    let mut registry = Registry::default();
+    let mut registrar = PluginRegistrar::new(&mut registry);
+    if !unsafe { init(&mut registrar) } {
+        let error = registry.error.unwrap_or_else(|| "kgames_plugin_init failed".to_owned());
+        anyhow::bail!(error);
+    }
+    add_registered(api, registry, Rc::new(library))
+}
+
+/// Add what a plugin registered to `api`, its functions keep `library` loaded.
+fn add_registered(api: &mut HostApi, registry: Registry, library: Rc<dyn Any>) -> anyhow::Result<()> {
+    for f in registry.functions {
+        let library = library.clone();
+        let PluginFn {
+            name,
+            params,
+            returns,
+            func,
+            data,
+        } = f;
+
+        let call = {
+            let name = name.clone();
+            move |_: &Context, args: Vec<Value>| {
+                let _ = &library;
+                let values: Vec<_> = args.iter().map(plugin_value).collect();
+                let mut result = PluginValue::UNIT;
+                // SAFETY: the strings of `values` live in `args` until the call returns
+                let ok = unsafe { func(data, values.as_ptr(), values.len(), &mut result) };
+
+                let expected = if ok { returns } else { TYPE_STRING };
+                let value = (result.ty == expected)
+                    .then(|| unsafe { read_value(&result) })
+                    .flatten();
+                match (ok, value) {
+                    (true, Some(value)) => Ok(value),
+                    (false, Some(Value::String(message))) => Err(message),
+                    (false, _) => Err(format!("{name}() failed")),
+                    (true, None) => Err(format!("{name}() returned a value of the wrong type")),
+                }
+            }
+        };
+        api.dynamic_function(&name, params, plugin_type(returns), Rc::new(call))
+            .map_err(anyhow::Error::msg)?;
+    }
+    for (name, ty, value) in registry.constants {
+        api.dynamic_constant(&name, ty, value);
+    }
+    Ok(())
+}
+
+/// Load every plugin in the `plugins` folder of `dirs`, adding their functions and constants to `api`.
+/// Plugins failing to load are skipped, with their error in `errors`. Returns the plugins loaded.
+pub fn load_plugins(dirs: &Dirs, api: &mut HostApi, errors: &mut ErrorMap) -> Vec<PathBuf> {
+    let dir = &dirs.plugins;
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return vec![],
+        Err(e) => {
+            errors.push((dir.clone(), EngineError::io(dir, e)));
+            return vec![];
+        }
+    };
+
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension() == Some(OsStr::new(env::consts::DLL_EXTENSION)))
+        .collect();
+    paths.sort();
+
+    paths.retain(|path| match load_plugin(api, path) {
+        Ok(()) => true,
+        Err(e) => {
+            errors.push((path.clone(), EngineError::Other(e.context("Failed to load plugin"))));
+            false
+        }
+    });
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+    use std::sync::OnceLock;
+
+    use super::*;
+
+    fn int(int: i64) -> PluginValue {
+        PluginValue {
+            ty: TYPE_INT,
+            int,
+            ..PluginValue::UNIT
+        }
+    }
+
+    unsafe extern "C" fn returns_string(
+        _: *mut c_void,
+        _: *const PluginValue,
+        _: usize,
+        result: *mut PluginValue,
+    ) -> bool {
+        let s = "not an int";
+        *result = PluginValue {
+            ty: TYPE_STRING,
+            string: s.as_ptr(),
+            len: s.len(),
+            ..PluginValue::UNIT
+        };
+        true
+    }
+
+    /// Register a function with the registrar given to plugins, returns its error if it fails.
+    fn register(
+        registry: &mut Registry,
+        name: *const c_char,
+        params: &[u32],
+        returns: u32,
+    ) -> Result<(), String> {
+        let mut registrar = PluginRegistrar::new(registry);
+        let register = registrar.register_function;
+        let data = ptr::null_mut();
+        let ok = unsafe {
+            register(
+                &mut registrar,
+                name,
+                params.as_ptr(),
+                params.len(),
+                returns,
+                returns_string,
+                data,
+            )
+        };
+        match ok {
+            true => Ok(()),
+            false => Err(registry.error.clone().unwrap_or_default()),
+        }
+    }
+
+    #[test]
+    fn register_functions() {
+        let mut registry = Registry::default();
+        assert!(register(
+            &mut registry,
+            c"f".as_ptr(),
+            &[TYPE_INT, TYPE_STRING],
+            TYPE_UNIT
+        )
+        .is_ok());
+
+        let err = register(
+            &mut registry,
+            c"many".as_ptr(),
+            &[TYPE_INT; MAX_PARAMS + 1],
+            TYPE_UNIT,
+        );
+        assert!(err.unwrap_err().contains("at most"));
+        let err = register(&mut registry, c"param".as_ptr(), &[TYPE_INT, 99], TYPE_UNIT);
+        assert!(err.unwrap_err().contains("unknown type"));
+        let err = register(&mut registry, c"result".as_ptr(), &[], 99);
+        assert!(err.unwrap_err().contains("unknown type"));
+        let err = register(&mut registry, ptr::null(), &[], TYPE_UNIT);
+        assert!(err.unwrap_err().contains("null"));
+
+        let names: Vec<_> = registry.functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["f"]);
+        assert_eq!(registry.functions[0].params, [Type::Int, Type::String]);
+    }
+
+    #[test]
+    fn register_constants() {
+        let mut registry = Registry::default();
+        let mut registrar = PluginRegistrar::new(&mut registry);
+        let register = registrar.register_constant;
+        unsafe {
+            assert!(register(&mut registrar, c"ANSWER".as_ptr(), int(42)));
+            assert!(!register(
+                &mut registrar,
+                c"UNIT".as_ptr(),
+                PluginValue::UNIT
+            ));
+            assert!(!register(&mut registrar, ptr::null(), int(1)));
+        }
+        assert!(
+            matches!(registry.constants[..], [(ref name, Type::Int, Value::Int(42))] if name == "ANSWER")
+        );
+    }
+
+    #[test]
+    fn result_of_the_wrong_type() {
+        let mut registry = Registry::default();
+        register(&mut registry, c"count".as_ptr(), &[], TYPE_INT).unwrap();
+        let mut api = HostApi::new();
+        add_registered(&mut api, registry, Rc::new(())).unwrap();
+
+        let count = api.overloads("count").next().unwrap();
+        let err = count
+            .call(&Context::new(env::temp_dir()), vec![])
+            .err()
+            .unwrap_or_default();
+        assert!(err.contains("wrong type"), "{err}");
+    }
+
+    /// Build the plugin in `engine/test_plugin`, once per set of features.
+    fn test_plugin(old_abi: bool) -> PathBuf {
+        static BUILT: [OnceLock<PathBuf>; 2] = [OnceLock::new(), OnceLock::new()];
+
+        BUILT[old_abi as usize]
+            .get_or_init(|| {
+                let target = env::temp_dir().join(format!("kgames-test-plugin-{}", old_abi as u8));
+                let manifest = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_plugin/Cargo.toml");
+                let mut build = Command::new(env!("CARGO"));
+                build
+                    .args(["build", "--quiet", "--manifest-path"])
+                    .arg(manifest);
+                build.arg("--target-dir").arg(&target);
+                if old_abi {
+                    build.args(["--features", "old-abi"]);
+                }
+                assert!(
+                    build.status().unwrap().success(),
+                    "failed to build the test plugin"
+                );
+
+                let name = format!(
+                    "{}test_plugin.{}",
+                    env::consts::DLL_PREFIX,
+                    env::consts::DLL_EXTENSION
+                );
+                target.join("debug").join(name)
+            })
+            .clone()
+    }
+
+    #[test]
+    fn load_test_plugin() {
+        let mut api = HostApi::new();
+        load_plugin(&mut api, &test_plugin(false)).unwrap();
+
+        let ctx = Context::new(env::temp_dir());
+        let call =
+            |name: &str, args: Vec<Value>| api.overloads(name).next().unwrap().call(&ctx, args);
+        assert!(matches!(
+            call("add", vec![Value::Int(2), Value::Int(3)]),
+            Ok(Value::Int(5))
+        ));
+        assert_eq!(call("fail", vec![]).err().unwrap_or_default(), "boom");
+        assert!(call("wrong_type", vec![])
+            .err()
+            .unwrap_or_default()
+            .contains("wrong type"));
+        assert!(api
+            .constants()
+            .iter()
+            .any(|(name, _, value)| name == "ANSWER" && matches!(value, Value::Int(42))));
+    }
+
+    #[test]
+    fn plugin_for_another_abi() {
+        let err = load_plugin(&mut HostApi::new(), &test_plugin(true)).unwrap_err();
+        assert!(err.to_string().contains("ABI version 0"), "{err}");
+    }
+}
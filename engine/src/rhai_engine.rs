@@ -1,54 +1,55 @@
 use super::*;
-use include_dir::{include_dir, Dir};
+use assets::{FontHandle, SoundHandle};
 use macroquad::prelude::*;
-use std::fs::{self, DirEntry};
+use std::collections::HashSet;
+use std::mem;
+use std::rc::Rc;
+use std::fs;
 use std::path::{Path, PathBuf};
 use std::time::{Duration, SystemTime};
 use std::{ffi::OsStr, io};
 
 use ffi::*;
+use rhai_modules::ModuleCache;
 
-// use game_core::texture::asset_store;
-// use crate::{ffi::*, reg_type, texture::asset_store};
-use rhai::{EvalAltResult, FuncArgs, ImmutableString, Scope, AST};
+use bindings::{Type, Value};
+use host::{Function, HostApi, HostValue, MAX_PARAMS};
+use rhai::{
+    ASTNode, Dynamic, EvalAltResult, Expr, FuncRegistration, NativeCallContext, Scope, Stmt, AST,
+};
 
 use console::Console;
 
-fn load_scripts_from_dir(
-    eng: &mut Engine,
+/// Functions handled by rhai itself, which are not registered anywhere.
+const BUILTIN_FUNCTIONS: [&str; 10] = [
+    "print",
+    "debug",
+    "type_of",
+    "eval",
+    "Fn",
+    "call",
+    "curry",
+    "is_def_fn",
+    "is_def_var",
+    "is_shared",
+];
+
+/// `missing` holds the scripts whose file is gone, a new file with the same contents is
+/// considered a rename and takes over the script, keeping its state.
+fn load_found_scripts<'a>(
+    eng: &mut Engine<'a>,
     console: &mut Console,
-    errors: &mut Vec<(PathBuf, anyhow::Error)>,
-    scripts: impl Iterator<Item = io::Result<DirEntry>>,
-    is_example_dir: bool,
+    errors: &mut ErrorMap,
+    missing: &mut Vec<Script<'a>>,
+    scripts: Vec<FoundScript>,
 ) -> anyhow::Result<()> {
     let mut result = Ok(());
-    let ext = OsStr::new("rhai");
 
-    for file in scripts {
-        if let Err(e) = file {
-            console.log(format!("Skipping file: {e}"));
-            continue;
-        }
-
-        let file = file.unwrap();
-        let path = file.path();
-
-        match path.extension() {
-            None => {
-                console.log(format!("Skipping file with no extension: {path:?}"));
-                continue;
-            }
-            Some(e) if e != ext => {
-                console.log(format!(
-                    "Skipping file with unknown(not .rhai) extension: {path:?}"
-                ));
-                continue;
-            }
-            _ => {} // Correct extension
-        }
+    for found in scripts {
+        let path = found.path;
 
         // Error handling
-        let mut add_err = |e| {
+        let mut add_err = |e: EngineError| {
             errors.push((path.clone(), e));
             if result.is_ok() {
                 result = Err(anyhow::anyhow!("Failed to load scripts"));
@@ -56,47 +57,89 @@ fn load_scripts_from_dir(
         };
 
         // Check for update
-        let metadata = file.metadata()?;
-        let modified = metadata.modified()?;
+        let modified = match path.metadata().and_then(|m| m.modified()) {
+            Ok(m) => m,
+            Err(e) => {
+                add_err(EngineError::io(&path, e));
+                continue;
+            }
+        };
 
         if let Some(existing) = eng.scripts.iter_mut().find(|s| s.path == path) {
-            if existing.modified != modified {
+            let imports_changed = eng.modules.imports_changed(existing.id.path());
+            if existing.modified != modified || imports_changed {
                 existing.modified = modified;
 
                 let contents = match fs::read_to_string(&path) {
                     Ok(c) => c,
                     Err(e) => {
-                        add_err(e.into());
+                        add_err(EngineError::io(&path, e));
                         continue;
                     }
                 };
+                existing.hash = source_hash(&contents);
 
-                existing.ast = match eng.engine.compile(contents) {
+                let mut ast = match eng.engine.compile(contents) {
                     Ok(a) => a,
                     Err(e) => {
-                        add_err(e.into());
+                        add_err(EngineError::Compile(parse_error(&path, e)));
                         continue;
                     }
                 };
-                existing.populate_scope();
+                ast.set_source(existing.id.to_string());
+
+                if imports_changed {
+                    console.log(format!("Reloading script {path:?}, one of its imports changed"));
+                } else {
+                    console.log(format!("Reloading changed script {path:?}"));
+                }
+                eng.loaded.push(existing.id.clone());
+                eng.ctx.set_running_script(&*existing);
+                eng.watchdog.start();
+                if let Err(e) = hot_swap(&eng.engine, existing, ast) {
+                    add_err(EngineError::Runtime(eval_error(&path, e)));
+                }
             }
             continue;
         }
 
-        // Add new script
-        console.log(format!("Adding new script {path:?}"));
-
         let contents = match fs::read_to_string(&path) {
             Ok(c) => c,
             Err(e) => {
-                add_err(e.into());
+                add_err(EngineError::io(&path, e));
                 continue;
             }
         };
+        let hash = source_hash(&contents);
+
+        // Move renamed script
+        if let Some(i) = missing.iter().position(|s| s.hash == hash) {
+            let mut script = missing.swap_remove(i);
+            console.log(format!("Renamed script {:?} to {path:?}", script.path));
+
+            let id = ScriptId::new(&path);
+            let old = mem::replace(&mut script.id, id);
+            eng.ctx.assets_mut().rename_owner(&old, &script.id);
+            eng.renamed.push((old, script.id.clone()));
+            script.ast.set_source(script.id.to_string());
+            script.path = path;
+            script.modified = modified;
+            script.is_example = found.example;
+            script.category = found.category;
+            script.game_dir = found.game_dir;
+            eng.scripts.push(script);
+            continue;
+        }
+
+        // Add new script
+        console.log(format!("Adding new script {path:?}"));
 
         // *** Compile script! *** //
         let mut script = Script::default();
-        script.is_example = is_example_dir;
+        script.api = Some(eng.api.clone());
+        script.is_example = found.example;
+        script.category = found.category;
+        script.game_dir = found.game_dir;
 
         // Disable optimizations
         eng.engine
@@ -104,7 +147,7 @@ fn load_scripts_from_dir(
         let ast = match eng.engine.compile(contents) {
             Ok(a) => a,
             Err(e) => {
-                add_err(e.into());
+                add_err(EngineError::Compile(parse_error(&path, e)));
                 continue;
             }
         };
@@ -113,31 +156,263 @@ fn load_scripts_from_dir(
         // Enable optimizations
         eng.engine
             .set_optimization_level(rhai::OptimizationLevel::Simple);
-        let ast = eng
+        let mut ast = eng
             .engine
             .optimize_ast(&script.scope, ast, eng.engine.optimization_level());
 
+        script.id = ScriptId::new(&path);
+        // Imports are resolved relative to the source
+        ast.set_source(script.id.to_string());
+
         script.ast = ast;
         script.modified = modified;
+        script.hash = hash;
         script.path = path;
 
         // Run code once
+        eng.ctx.set_running_script(&script);
+        eng.watchdog.start();
         if let Err(e) = eng
             .engine
             .run_ast_with_scope(&mut script.scope, &script.ast)
         {
-            let e = anyhow::anyhow!("Failed to init script: {e}");
-            result = Err(anyhow::anyhow!("{e}"));
-            errors.push((script.path.clone(), e.into()));
+            result = Err(anyhow::anyhow!("Failed to init script"));
+            errors.push((script.path.clone(), EngineError::Runtime(eval_error(&script.path, e))));
             continue;
         }
 
+        eng.loaded.push(script.id.clone());
         eng.scripts.push(script);
     }
 
     result
 }
 
+/// Replace the AST of a script, keeping the values of variables whose name and type did not change.
+/// The script is left untouched if running the new top level code fails.
+fn hot_swap(engine: &rhai::Engine, script: &mut Script, ast: AST) -> Result<(), Box<EvalAltResult>> {
+    let old_ast = mem::replace(&mut script.ast, ast);
+    let old_scope = mem::take(&mut script.scope);
+
+    script.populate_scope();
+    if let Err(e) = engine.run_ast_with_scope(&mut script.scope, &script.ast) {
+        script.ast = old_ast;
+        script.scope = old_scope;
+        return Err(e);
+    }
+
+    for (name, is_constant, value) in old_scope.iter() {
+        if is_constant || script.scope.is_constant(name) != Some(false) {
+            continue;
+        }
+        if let Some(new) = script.scope.get_mut(name) {
+            if new.type_name() == value.type_name() {
+                *new = value;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The context of the engine running a script, set as the default tag of the rhai engine.
+fn context(nc: &NativeCallContext) -> Result<Context, Box<EvalAltResult>> {
+    nc.tag()
+        .and_then(|tag| tag.read_lock::<Context>())
+        .map(|ctx| ctx.clone())
+        .ok_or_else(|| external_error("the engine context is missing"))
+}
+
+/// Register every function of `api`, overloads with as many parameters share one function.
+fn register_functions(engine: &mut rhai::Engine, api: &Rc<HostApi>) {
+    let mut functions = vec![];
+    for f in api.functions() {
+        if !functions.contains(&(f.name.as_str(), f.params.len())) {
+            functions.push((f.name.as_str(), f.params.len()));
+        }
+    }
+
+    for (name, arity) in functions {
+        let overloads: Vec<_> = api.overloads(name).filter(|f| f.params.len() == arity).collect();
+        let registration = FuncRegistration::new(name).with_params_info(params_info(&overloads));
+        let (api, function) = (api.clone(), name.to_owned());
+        let call = move |nc: NativeCallContext, args: Vec<Dynamic>| {
+            api.call(&context(&nc)?, &function, &args, to_value)
+                .map(from_value)
+                .map_err(external_error)
+        };
+
+        macro_rules! register {
+            ($($arg:ident),*) => {{
+                registration
+                    .register_into_engine(engine, move |nc: NativeCallContext, $($arg: Dynamic),*| {
+                        call(nc, vec![$($arg),*])
+                    });
+            }};
+        }
+        match arity {
+            0 => register!(),
+            1 => register!(a),
+            2 => register!(a, b),
+            3 => register!(a, b, c),
+            4 => register!(a, b, c, d),
+            5 => register!(a, b, c, d, e),
+            6 => register!(a, b, c, d, e, f),
+            7 => register!(a, b, c, d, e, f, g),
+            8 => register!(a, b, c, d, e, f, g, h),
+            _ => unreachable!("HostApi rejects functions with more than {MAX_PARAMS} parameters"),
+        };
+    }
+}
+
+/// Parameters and return type for the editor definitions, `a | b` where overloads differ.
+fn params_info(overloads: &[&Function]) -> Vec<String> {
+    let Some(first) = overloads.first() else {
+        return vec![];
+    };
+    let either = |types: Vec<String>| {
+        let mut unique = vec![];
+        for ty in types {
+            if !unique.contains(&ty) {
+                unique.push(ty);
+            }
+        }
+        unique.join(" | ")
+    };
+
+    let mut info: Vec<_> = first
+        .params
+        .iter()
+        .enumerate()
+        .map(|(i, (name, _))| {
+            let types = overloads.iter().map(|b| b.params[i].1.to_string()).collect();
+            format!("{name}: {}", either(types))
+        })
+        .collect();
+    let returns = overloads
+        .iter()
+        .map(|b| b.returns.map_or("()".to_owned(), |ty| ty.to_string()))
+        .collect();
+    info.push(either(returns));
+    info
+}
+
+/// Convert an argument for a parameter of type `ty`.
+fn to_value(arg: &Dynamic, ty: Type) -> Option<Value> {
+    let value = match ty {
+        Type::Unit => arg.is_unit().then_some(Value::Unit)?,
+        Type::Float => match arg.as_float() {
+            Ok(v) => Value::Float(v),
+            Err(_) => Value::Int(arg.as_int().ok()?),
+        },
+        Type::Int => Value::Int(arg.as_int().ok()?),
+        Type::Bool => Value::Bool(arg.as_bool().ok()?),
+        Type::String => Value::String(arg.clone().into_string().ok()?),
+        Type::Strings => Value::Strings(
+            arg.clone()
+                .try_cast::<rhai::Array>()?
+                .into_iter()
+                .map(|s| s.into_string().ok())
+                .collect::<Option<_>>()?,
+        ),
+        Type::Vec2 => Value::Vec2(arg.clone().try_cast()?),
+        Type::Vec3 => Value::Vec3(arg.clone().try_cast()?),
+        Type::Rect => Value::Rect(arg.clone().try_cast()?),
+        Type::Color => Value::Color(arg.clone().try_cast()?),
+        Type::Texture => Value::Texture(arg.clone().try_cast()?),
+        Type::Sound => Value::Sound(arg.clone().try_cast()?),
+        Type::Font => Value::Font(arg.clone().try_cast()?),
+        Type::Key => Value::Key(arg.clone().try_cast()?),
+        Type::Mouse => Value::Mouse(arg.clone().try_cast()?),
+        Type::Host(name) => Value::Host(arg.clone().try_cast::<HostValue>().filter(|v| v.name == name)?),
+        Type::Optional(_) if arg.is_unit() => Value::Unit,
+        Type::Optional(ty) => to_value(arg, *ty)?,
+    };
+    Some(value)
+}
+
+fn from_value(value: Value) -> Dynamic {
+    match value {
+        Value::Unit => Dynamic::UNIT,
+        Value::Float(v) => v.into(),
+        Value::Int(v) => v.into(),
+        Value::Bool(v) => v.into(),
+        Value::String(v) => v.into(),
+        Value::Strings(v) => v.into_iter().map(Dynamic::from).collect::<rhai::Array>().into(),
+        Value::Vec2(v) => Dynamic::from(v),
+        Value::Vec3(v) => Dynamic::from(v),
+        Value::Rect(v) => Dynamic::from(v),
+        Value::Color(v) => Dynamic::from(v),
+        Value::Texture(v) => Dynamic::from(v),
+        Value::Sound(v) => Dynamic::from(v),
+        Value::Font(v) => Dynamic::from(v),
+        Value::Key(v) => Dynamic::from(v),
+        Value::Mouse(v) => Dynamic::from(v),
+        Value::Host(v) => Dynamic::from(v),
+    }
+}
+
+/// Runtime error thrown in the script, for failures of the functions it calls.
+pub(crate) fn external_error(e: impl ToString) -> Box<EvalAltResult> {
+    e.to_string().into()
+}
+
+fn position(pos: rhai::Position) -> (usize, usize) {
+    (pos.line().unwrap_or(0), pos.position().unwrap_or(0))
+}
+
+fn parse_error(path: &Path, rhai::ParseError(err, pos): rhai::ParseError) -> ScriptError {
+    let (line, column) = position(pos);
+    ScriptError {
+        line,
+        column,
+        ..ScriptError::new(path, err)
+    }
+}
+
+/// Unwrap nested function call errors into a call stack.
+fn eval_error(path: &Path, err: Box<EvalAltResult>) -> ScriptError {
+    let mut call_stack = vec![];
+    let mut err = *err;
+
+    let mut path = path.to_path_buf();
+
+    loop {
+        let (function, inner, pos) = match err {
+            EvalAltResult::ErrorInFunctionCall(function, _, inner, pos) => (function, inner, pos),
+            // The error is inside the imported file
+            EvalAltResult::ErrorInModule(module, inner, pos) => {
+                let frame = format!("import {module:?}");
+                path = PathBuf::from(module);
+                (frame, inner, pos)
+            }
+            _ => break,
+        };
+        let (line, column) = position(pos);
+        call_stack.push(StackFrame {
+            function,
+            line,
+            column,
+        });
+        err = *inner;
+    }
+
+    let (line, column) = position(err.take_position());
+    let message = match err {
+        // Stopped by the watchdog, which explains why
+        EvalAltResult::ErrorTerminated(reason, _) => reason.to_string(),
+        err => err.to_string(),
+    };
+    ScriptError {
+        line,
+        column,
+        call_stack,
+        ..ScriptError::new(path, message)
+    }
+}
+
+/// Register types with getters and setters for their fields, or getters calling their methods:
+/// `Vec2 as "Vec2" = x, y;` or `Texture2D = width(), height();`.
 macro_rules! reg_type {
     (
         $engine: expr => {
@@ -148,20 +423,17 @@ macro_rules! reg_type {
         }
     ) => {
         $(
-            $(
-                $engine.register_type_with_name::<$name>($exposed_name);
-
-                // Register get/set for each field or method
-                $(
-                    $engine.register_get_set(
-                        stringify!($field),
-                        |_self: &mut $name| _self.$field,
-                        |_self: &mut $name, new| _self.$field = new
-                    );
-                )*
+            $engine.register_type_with_name::<$name>($exposed_name);
 
-            )*
-        )?
+            // Register get/set for each field
+            $($(
+                $engine.register_get_set(
+                    stringify!($field),
+                    |_self: &mut $name| _self.$field,
+                    |_self: &mut $name, new| _self.$field = new
+                );
+            )*)?
+        )*
     };
     // Getters via methods
     (
@@ -174,7 +446,7 @@ macro_rules! reg_type {
        $(
            $(
                 $engine.register_get(
-                    stringify!($field),
+                    stringify!($method),
                     |_self: &mut $name| _self.$method(),
                 );
            )*
@@ -184,37 +456,63 @@ macro_rules! reg_type {
 
 #[derive(Clone)]
 pub struct Script<'a> {
+    id: ScriptId,
     path: PathBuf,
+    category: String,
+    /// Folder of a folder-per-game
+    game_dir: Option<PathBuf>,
     modified: SystemTime,
+    /// Hash of the source, used to detect renames
+    hash: u64,
     pub ast: AST,
     //TODO:
     pub scope: Scope<'a>,
     pub is_example: bool,
+    /// For the constants added by the application
+    pub(crate) api: Option<Rc<HostApi>>,
 }
 
 impl<'a> Default for Script<'a> {
     fn default() -> Self {
         Self {
+            id: ScriptId::default(),
             path: PathBuf::new(),
+            category: String::new(),
+            game_dir: None,
             modified: SystemTime::now(),
+            hash: 0,
             ast: AST::empty(),
             scope: Scope::new(),
             is_example: false,
+            api: None,
         }
     }
 }
 impl<'a> GameScript for Script<'a> {
+    fn id<'i>(&'i self) -> &'i ScriptId {
+        &self.id
+    }
     fn path<'p>(&'p self) -> &'p Path {
         &self.path
     }
     fn name<'n>(&'n self) -> Option<&'n str> {
-        self.path.file_name().and_then(OsStr::to_str)
+        match self.game_dir {
+            Some(ref dir) => dir.file_name().and_then(OsStr::to_str),
+            None => self.path.file_name().and_then(OsStr::to_str),
+        }
+    }
+    fn category<'c>(&'c self) -> &'c str {
+        &self.category
+    }
+    fn game_dir<'d>(&'d self) -> Option<&'d Path> {
+        self.game_dir.as_deref()
     }
     fn is_example(&self) -> bool {
         self.is_example
     }
-    fn reset(&mut self) {
+    fn reset(&mut self) -> anyhow::Result<()> {
         self.scope.clear();
+        Ok(())
     }
     fn populate_scope(&mut self) {
         for (name, color) in COLORS {
@@ -226,6 +524,9 @@ impl<'a> GameScript for Script<'a> {
         for (name, key) in MOUSE_BUTTONS {
             self.scope.push_constant(name, key);
         }
+        for (name, _, value) in self.api.iter().flat_map(|api| api.constants()) {
+            self.scope.push_constant_dynamic(name.clone(), from_value(value.clone()));
+        }
         self.ast
             .iter_literal_variables(true, true)
             .for_each(|(name, is_const, val)| {
@@ -238,113 +539,73 @@ impl<'a> GameScript for Script<'a> {
     }
 }
 
+/// Operations between two checks of the watchdog
+const WATCHDOG_INTERVAL: u64 = 1024;
+
 pub struct Engine<'a> {
     pub engine: rhai::Engine,
     pub scripts: Vec<Script<'a>>,
+    /// Scripts renamed by the last load, as `(old, new)` ids
+    renamed: Vec<(ScriptId, ScriptId)>,
+    /// Scripts whose top level code ran during the last load
+    loaded: Vec<ScriptId>,
+    modules: ModuleCache,
+    watchdog: Rc<Watchdog>,
+    api: Rc<HostApi>,
+    ctx: Context,
 }
 impl<'a> Engine<'a> {
-    pub fn new() -> Self {
-        Self {
-            engine: rhai::Engine::new(),
+    pub fn new(ctx: Context) -> Self {
+        let modules = ModuleCache::new(ctx.dirs().root.clone());
+        let watchdog = Rc::new(Watchdog::default());
+
+        let mut engine = rhai::Engine::new();
+        engine.set_module_resolver(modules.clone());
+        // Functions get the context from the tag, see `context`
+        engine.set_default_tag(Dynamic::from(ctx.clone()));
+        // Logged for the running script instead of stdout, which release builds on Windows do not have
+        let log = ctx.clone();
+        engine.on_print(move |text| log.script_log(LogLevel::Info, text));
+        let log = ctx.clone();
+        engine.on_debug(move |text, _, pos| match pos.line() {
+            Some(line) => log.script_log(LogLevel::Debug, format!("{text} (line {line})")),
+            None => log.script_log(LogLevel::Debug, text),
+        });
+
+        let wd = watchdog.clone();
+        engine.on_progress(move |operations| {
+            if operations % WATCHDOG_INTERVAL != 0 || !wd.expired() {
+                return None;
+            }
+            Some(wd.abort_message().into())
+        });
+
+        let mut engine = Self {
+            engine,
             scripts: vec![],
-        }
+            renamed: vec![],
+            loaded: vec![],
+            modules,
+            watchdog,
+            api: Rc::new(HostApi::new()),
+            ctx,
+        };
+        engine.set_limits(&Limits::default());
+        engine
     }
 }
 
 impl<'a> ScriptEngine for Engine<'a> {
     type Script = Script<'a>;
 
-    fn extension<'b>() -> &'b str {
-        "rhai"
+    fn extensions() -> &'static [&'static str] {
+        &["rhai"]
     }
-    fn expose_layer(&mut self) {
-        self.engine
-            // Actions
-            .register_fn("clear", clear_background)
-            .register_fn(
-                "text",
-                |t: ImmutableString, x: f32, y: f32, sz: f32, tint: Color| {
-                    draw_text(t.as_str(), x, y, sz, tint);
-                },
-            )
-            .register_fn("circle", draw_circle)
-            .register_fn("line", draw_line)
-            .register_fn("triangle", draw_triangle)
-            .register_fn("rectangle", draw_rectangle)
-            .register_fn("rectangle_lines", draw_rectangle_lines)
-            .register_fn("msgbox", |title: ImmutableString, msg: ImmutableString| {
-                let _ = msgbox::create(title.as_str(), msg.as_str(), msgbox::IconType::Info);
-            })
-            .register_fn("overlaps", Rect::overlaps)
-            .register_fn("overlaps", |a: Rect, b: Rect| a.overlaps(&b))
-            .register_fn("texture", draw_texture)
-            .register_fn("texture", draw_texture_stored)
-            .register_fn(
-                "texture_ex",
-                |t: &Texture2D, x: f32, y: f32, tint: Color, dest_size: Vec2, rotation: f32| {
-                    draw_texture_ex(
-                        t,
-                        x,
-                        y,
-                        tint,
-                        DrawTextureParams {
-                            dest_size: Some(dest_size),
-                            source: None,
-                            rotation,
-                            flip_x: false,
-                            flip_y: false,
-                            pivot: None,
-                        },
-                    )
-                },
-            )
-            .register_fn(
-                "texture_pro",
-                |t: &Texture2D,
-                 x: f32,
-                 y: f32,
-                 tint: Color,
-                 dest_size: Vec2,
-                 source: Rect,
-                 rotation: f32,
-                 pivot: Vec2| {
-                    draw_texture_ex(
-                        t,
-                        x,
-                        y,
-                        tint,
-                        DrawTextureParams {
-                            dest_size: Some(dest_size),
-                            source: Some(source),
-                            rotation,
-                            flip_x: false,
-                            flip_y: false,
-                            pivot: Some(pivot),
-                        },
-                    )
-                },
-            )
-            // textures
-            .register_fn("load_texture", load_texture_sync)
-            .register_fn("get_texture", load_texture_stored)
-            // Information
-            .register_fn("deltatime", get_frame_time)
-            .register_fn("screen_width", screen_width)
-            .register_fn("screen_height", screen_height)
-            .register_fn("last_keypress", get_last_key_pressed)
-            .register_fn("key_down", is_key_down)
-            .register_fn("key_pressed", is_key_pressed)
-            .register_fn("key_released", is_key_released)
-            .register_fn("mouse_down", is_mouse_button_down)
-            .register_fn("mouse_pressed", is_mouse_button_pressed)
-            .register_fn("mouse_released", is_mouse_button_released)
-            .register_fn("fps", get_fps)
-            // Getters/Setters
-            .register_fn("vec2", vec2)
-            .register_fn("vec3", vec3)
-            .register_fn("rect", Rect::new)
-            .register_fn("color", Color::new);
-
+    fn context(&self) -> &Context {
+        &self.ctx
+    }
+    fn expose_api(&mut self, api: &HostApi) {
+        // NOTE: types are registered first, so the names show up in the editor definitions
         // Fields
         reg_type! {
             self.engine => {
@@ -354,7 +615,10 @@ impl<'a> ScriptEngine for Engine<'a> {
                 Color as "Color" = r, g, b, a;
                 KeyCode as "Key";
                 Texture2D as "Texture";
+                SoundHandle as "Sound";
+                FontHandle as "Font";
                 MouseButton as "Mouse";
+                HostValue as "HostValue";
             }
         }
 
@@ -365,22 +629,153 @@ impl<'a> ScriptEngine for Engine<'a> {
                 Rect = size(), center();
             }
         }
+
+        // Compared like the numbers they are in the other languages
+        self.engine
+            .register_fn("==", |a: KeyCode, b: KeyCode| a == b)
+            .register_fn("!=", |a: KeyCode, b: KeyCode| a != b)
+            .register_fn("==", |a: MouseButton, b: MouseButton| a == b)
+            .register_fn("!=", |a: MouseButton, b: MouseButton| a != b);
+
+        self.api = Rc::new(api.clone());
+        self.modules.set_api(self.api.clone());
+        register_functions(&mut self.engine, &self.api);
+    }
+
+    fn set_limits(&mut self, limits: &Limits) {
+        self.engine
+            .set_max_operations(limits.max_operations)
+            .set_max_call_levels(limits.max_call_depth)
+            .set_max_expr_depths(limits.max_expr_depth, limits.max_function_expr_depth)
+            .set_max_string_size(limits.max_string_size)
+            .set_max_array_size(limits.max_array_size)
+            .set_max_map_size(limits.max_map_size);
+        self.watchdog
+            .set_budget(Duration::from_millis(limits.frame_budget_ms));
     }
 
-    fn call_function(&mut self, script_index: usize, name: impl AsRef<str>) -> anyhow::Result<()> {
-        let script = &mut self.scripts[script_index];
+    fn call_function(&mut self, id: &ScriptId, name: impl AsRef<str>) -> anyhow::Result<()> {
+        let name = name.as_ref();
+        if !self.has_function(id, name) {
+            return Err(EngineError::MissingHook {
+                path: id.path().to_path_buf(),
+                hook: name.to_owned(),
+            }
+            .into());
+        }
+        let script = find_script(&mut self.scripts, id)?;
+        self.ctx.set_running_script(&*script);
+        self.watchdog.start();
         self.engine
             .call_fn::<()>(
-                &mut script.scope, // NOTE: possible OOB
+                &mut script.scope,
                 &script.ast,
                 name,
                 (), // IMPORTANT: no args are passed
             )
-            .map_err(|e| anyhow::anyhow!("{e}"))
+            .map_err(|e| EngineError::Runtime(eval_error(&script.path, e)).into())
+    }
+
+    fn has_function(&mut self, id: &ScriptId, name: &str) -> bool {
+        find_script(&mut self.scripts, id).is_ok_and(|script| {
+            script
+                .ast
+                .iter_functions()
+                .any(|f| f.name == name && f.params.is_empty())
+        })
     }
 
-    fn scripts<'s>(&'s mut self) -> &'s mut [Self::Script] {
-        &mut self.scripts
+    fn restart_script(&mut self, id: &ScriptId) -> anyhow::Result<()> {
+        let script = find_script(&mut self.scripts, id)?;
+        self.ctx.set_running_script(&*script);
+        script.reset()?;
+        script.populate_scope();
+
+        self.watchdog.start();
+        self.engine
+            .run_ast_with_scope(&mut script.scope, &script.ast)
+            .map_err(|e| EngineError::Runtime(eval_error(&script.path, e)).into())
+    }
+
+    fn check_script(&self, path: &Path, source: &[u8]) -> Vec<Diagnostic> {
+        let source = match std::str::from_utf8(source) {
+            Ok(source) => source,
+            Err(e) => return vec![Diagnostic::error(path, e)],
+        };
+        let ast = match self.engine.compile(source) {
+            Ok(ast) => ast,
+            Err(rhai::ParseError(err, pos)) => {
+                let diagnostic = Diagnostic::error(path, err)
+                    .at(pos.line().unwrap_or(0), pos.position().unwrap_or(0));
+                return vec![diagnostic];
+            }
+        };
+
+        let hooks: Vec<_> = ast
+            .iter_functions()
+            .filter(|f| f.params.is_empty())
+            .map(|f| f.name)
+            .collect();
+        let mut diagnostics = check::missing_hooks(path, hooks.iter().copied());
+
+        // Calls to functions that are neither registered nor defined in the script.
+        let registered: HashSet<String> = self
+            .engine
+            .gen_fn_signatures(true)
+            .into_iter()
+            .filter_map(|sig| sig.split('(').next().map(str::to_owned))
+            .collect();
+        let defined: HashSet<&str> = ast.iter_functions().map(|f| f.name).collect();
+
+        ast.walk(&mut |nodes: &[ASTNode]| {
+            let call = match nodes.last() {
+                Some(ASTNode::Expr(Expr::FnCall(call, pos)))
+                | Some(ASTNode::Stmt(Stmt::FnCall(call, pos))) => Some((call, pos)),
+                _ => None,
+            };
+            if let Some((call, pos)) = call {
+                let name = call.name.as_str();
+                // NOTE: functions of imported modules are only known at runtime
+                let known = call.op_token.is_some()
+                    || !call.namespace.is_empty()
+                    || BUILTIN_FUNCTIONS.contains(&name)
+                    || defined.contains(name)
+                    || registered.contains(name);
+
+                if !known {
+                    diagnostics.push(
+                        Diagnostic::warning(path, format!("call to unknown function `{name}`"))
+                            .at(pos.line().unwrap_or(0), pos.position().unwrap_or(0)),
+                    );
+                }
+            }
+            true
+        });
+
+        diagnostics
+    }
+
+    fn api_reference(&self) -> ApiReference {
+        ApiReference::new("rhai", &self.api)
+    }
+
+    fn definitions(&self) -> Option<(&'static str, String)> {
+        let mut constants = Script::default();
+        constants.api = Some(self.api.clone());
+        constants.populate_scope();
+
+        let definitions = self
+            .engine
+            .definitions_with_scope(&constants.scope)
+            .with_headers(true)
+            .include_standard_packages(false)
+            .single_file();
+
+        Some(("kgames.d.rhai", definitions))
+    }
+
+    fn scripts<'s>(&'s mut self) -> Vec<&'s mut Self::Script> {
+        self.scripts.iter_mut().collect()
     }
 
     fn load_scripts(
@@ -390,61 +785,52 @@ impl<'a> ScriptEngine for Engine<'a> {
         from: &[ScriptDir],
     ) -> anyhow::Result<()> {
         let mut result = Ok(());
-        let mut is_example_dir = false;
 
         for source in from {
-            let src = source.path();
+            console.log(format!("==> Loading scripts from {:?}", source.path(self.ctx.dirs())));
+        }
+        let first_error = errors.len();
+        let found = find_scripts(self.ctx.dirs(), from, Self::extensions(), errors);
+        for (path, e) in &errors[first_error..] {
+            console.err(format!("Failed to read dir {path:?}: {}", e.message()));
+            result = Err(anyhow::anyhow!("Failed to read script folders"));
+        }
 
-            console.log(format!("==> Loading scripts from {src:?}"));
+        // Scripts that are not found anymore were either renamed or deleted
+        let (mut missing, scripts) = mem::take(&mut self.scripts).into_iter().partition(|s: &Script| {
+            let dir = if s.is_example {
+                ScriptDir::Examples
+            } else {
+                ScriptDir::Scripts
+            };
+            let searched = from.contains(&dir) || !s.path.is_file();
+            searched && !found.iter().any(|f| f.path == s.path)
+        });
+        self.scripts = scripts;
+        self.renamed.clear();
+        self.loaded.clear();
+
+        if let Err(e) = load_found_scripts(self, console, errors, &mut missing, found) {
+            result = Err(e);
+        }
 
-            match fs::read_dir(src) {
-                Ok(scripts) => {
-                    if let Err(e) =
-                        load_scripts_from_dir(self, console, errors, scripts, is_example_dir)
-                    {
-                        result = Err(e);
-                    }
-                }
-                Err(e) => {
-                    console.err(format!("Failed to read dir {src:?}: {e}"));
-                    result = Err(e.into());
-                }
-            }
+        for script in missing {
+            console.log(format!("Removing deleted script {:?}", script.path));
+            self.ctx.assets_mut().release(&script.id);
         }
+
         result
     }
 
-    fn reload_scripts(
-        &mut self,
-        console: &mut Console,
-        errors: &mut ErrorMap,
-    ) -> anyhow::Result<()> {
-        // NOTE: useless!
-        self.load_scripts(console, errors, &[ScriptDir::Scripts, ScriptDir::Examples])
+    fn renamed_scripts(&self) -> &[(ScriptId, ScriptId)] {
+        &self.renamed
     }
 
-    fn write_examples(&mut self, warnings: &mut Vec<String>) -> Result<(), Vec<io::Error>> {
-        static EXAMPLES: Dir = include_dir!("$CARGO_MANIFEST_DIR/../res/examples");
-
-        let mut errors = Vec::with_capacity(EXAMPLES.files().count());
-
-        for example in EXAMPLES.files() {
-            let write_path = dirs().examples.join(example.path());
-
-            if write_path.is_file() {
-                warnings.push(format!("File {write_path:?} already exists. To overwrite the example, rename or delete it."));
-                continue;
-            }
-
-            if let Err(e) = fs::write(write_path, example.contents()) {
-                errors.push(e);
-            }
-        }
+    fn loaded_scripts(&self) -> &[ScriptId] {
+        &self.loaded
+    }
 
-        if errors.len() > 0 {
-            Err(errors)
-        } else {
-            Ok(())
-        }
+    fn write_examples(&mut self, warnings: &mut Vec<String>) -> Result<(), Vec<io::Error>> {
+        write_examples(self.ctx.dirs(), Self::extension(), warnings)
     }
 }
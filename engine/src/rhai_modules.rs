@@ -0,0 +1,257 @@
+//! Module imports for rhai scripts, restricted to the data directory.
+//!
+//! `import "lib/physics" as phys;` looks for `lib/physics.rhai` next to the importing file,
+//! then in the data directory. Compiled modules are shared by all the scripts until their file changes.
+
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::SystemTime;
+
+use rhai::{EvalAltResult, Module, ModuleResolver, Position, Scope, Shared};
+
+use super::*;
+use crate::rhai_engine::{external_error, Script};
+
+struct CachedModule {
+    modified: SystemTime,
+    module: Shared<Module>,
+}
+
+#[derive(Default)]
+struct Cache {
+    modules: HashMap<PathBuf, CachedModule>,
+    /// Modules imported by each script or module
+    imports: HashMap<PathBuf, BTreeSet<PathBuf>>,
+    /// Modules being compiled, to detect cyclic imports
+    loading: Vec<PathBuf>,
+    /// For the constants added by the application
+    api: Option<Rc<HostApi>>,
+    /// Data directory, modules can not be imported from outside of it
+    root: PathBuf,
+}
+
+/// Handle to the module cache, shared between the rhai engine and [`Engine`](super::rhai_engine::Engine).
+#[derive(Clone)]
+pub struct ModuleCache(Rc<RefCell<Cache>>);
+
+impl ModuleCache {
+    pub fn new(root: PathBuf) -> Self {
+        Self(Rc::new(RefCell::new(Cache {
+            root,
+            ..Cache::default()
+        })))
+    }
+
+    pub fn set_api(&self, api: Rc<HostApi>) {
+        self.0.borrow_mut().api = Some(api);
+    }
+
+    /// Forget the imports of `importer`, they are recorded again when its top level code runs.
+    fn forget_imports(&self, importer: &Path) {
+        self.0.borrow_mut().imports.remove(importer);
+    }
+
+    /// Whether any module imported by `importer`, directly or not, changed since it was compiled.
+    pub fn imports_changed(&self, importer: &Path) -> bool {
+        let cache = self.0.borrow();
+        let mut visited = vec![];
+        let mut pending: Vec<&Path> = vec![importer];
+
+        while let Some(path) = pending.pop() {
+            let Some(imports) = cache.imports.get(path) else {
+                continue;
+            };
+            for import in imports {
+                if visited.contains(&import) {
+                    continue;
+                }
+                visited.push(import);
+
+                let modified = import.metadata().and_then(|m| m.modified()).ok();
+                let cached = cache.modules.get(import).map(|m| m.modified);
+                if modified.is_none() || modified != cached {
+                    return true;
+                }
+                pending.push(import);
+            }
+        }
+        false
+    }
+
+    /// Turn the path of an import into the canonical path of a file inside the data directory.
+    fn resolve_path(
+        &self,
+        source: Option<&str>,
+        path: &str,
+        pos: Position,
+    ) -> Result<PathBuf, Box<EvalAltResult>> {
+        let denied = |reason: String| {
+            Box::new(EvalAltResult::ErrorRuntime(
+                format!("Can not import {path:?}, {reason}").into(),
+                pos,
+            ))
+        };
+
+        let relative = Path::new(path);
+        if !is_contained(relative) {
+            return Err(denied(
+                "only relative paths without '..' can be imported".to_owned(),
+            ));
+        }
+
+        let root = self
+            .0
+            .borrow()
+            .root
+            .canonicalize()
+            .map_err(|e| denied(format!("failed to find the data directory: {e}")))?;
+        let file = relative.with_extension("rhai");
+
+        let importer_dir = source.and_then(|s| Path::new(s).parent());
+        for base in importer_dir.into_iter().chain([root.as_path()]) {
+            let Ok(candidate) = base.join(&file).canonicalize() else {
+                continue;
+            };
+            // NOTE: symlinks could still point outside
+            if !candidate.starts_with(&root) {
+                return Err(denied("the file is outside of the data directory".to_owned()));
+            }
+            return Ok(candidate);
+        }
+
+        Err(Box::new(EvalAltResult::ErrorModuleNotFound(path.to_owned(), pos)))
+    }
+
+    fn compile(
+        &self,
+        engine: &rhai::Engine,
+        path: &Path,
+        pos: Position,
+    ) -> Result<Shared<Module>, Box<EvalAltResult>> {
+        let in_module = |e: Box<EvalAltResult>| {
+            Box::new(EvalAltResult::ErrorInModule(path.display().to_string(), e, pos))
+        };
+
+        let modified = path
+            .metadata()
+            .and_then(|m| m.modified())
+            .map_err(|e| in_module(external_error(e)))?;
+        let contents = fs::read_to_string(path).map_err(|e| in_module(external_error(e)))?;
+
+        let mut ast = engine
+            .compile(contents)
+            .map_err(|e| in_module(EvalAltResult::from(e).into()))?;
+        ast.set_source(path.to_string_lossy().as_ref());
+
+        // Modules see the same constants as scripts
+        let mut constants = Script::default();
+        constants.api = self.0.borrow().api.clone();
+        constants.populate_scope();
+        let scope: Scope = constants.scope;
+
+        self.0.borrow_mut().loading.push(path.to_path_buf());
+        let module = Module::eval_ast_as_new(scope, &ast, engine);
+        self.0.borrow_mut().loading.pop();
+
+        let module: Shared<Module> = module.map_err(in_module)?.into();
+        self.0.borrow_mut().modules.insert(
+            path.to_path_buf(),
+            CachedModule {
+                modified,
+                module: module.clone(),
+            },
+        );
+        Ok(module)
+    }
+}
+
+impl ModuleResolver for ModuleCache {
+    fn resolve(
+        &self,
+        engine: &rhai::Engine,
+        source: Option<&str>,
+        path: &str,
+        pos: Position,
+    ) -> Result<Shared<Module>, Box<EvalAltResult>> {
+        let path = self.resolve_path(source, path, pos)?;
+
+        if let Some(source) = source {
+            self.0
+                .borrow_mut()
+                .imports
+                .entry(PathBuf::from(source))
+                .or_default()
+                .insert(path.clone());
+        }
+
+        if self.0.borrow().loading.contains(&path) {
+            return Err(Box::new(EvalAltResult::ErrorModuleNotFound(
+                format!("Cyclic import of {path:?}"),
+                pos,
+            )));
+        }
+
+        // Cache hit
+        let modified = path.metadata().and_then(|m| m.modified()).ok();
+        if let Some(cached) = self.0.borrow().modules.get(&path) {
+            if Some(cached.modified) == modified {
+                return Ok(cached.module.clone());
+            }
+        }
+
+        // Imports of the module are recorded again while compiling
+        self.forget_imports(&path);
+        self.compile(engine, &path, pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::TempDir;
+
+    fn modules(name: &str) -> (TempDir, Context, ModuleCache) {
+        let tmp = TempDir::new(name);
+        let ctx = Context::new(tmp.0.join("data"));
+        ctx.dirs().create().unwrap();
+        fs::write(ctx.dirs().lib.join("physics.rhai"), "fn gravity() { 9.8 }").unwrap();
+        fs::write(tmp.0.join("secret.rhai"), "").unwrap();
+        let modules = ModuleCache::new(ctx.dirs().root.clone());
+        (tmp, ctx, modules)
+    }
+
+    fn denied(result: Result<PathBuf, Box<EvalAltResult>>) -> bool {
+        matches!(*result.unwrap_err(), EvalAltResult::ErrorRuntime(..))
+    }
+
+    #[test]
+    fn module_in_data_directory() {
+        let (_tmp, ctx, modules) = modules("module-found");
+        let path = modules.resolve_path(None, "lib/physics", Position::NONE).unwrap();
+        assert_eq!(path, ctx.dirs().lib.join("physics.rhai").canonicalize().unwrap());
+
+        let err = modules.resolve_path(None, "lib/missing", Position::NONE).unwrap_err();
+        assert!(matches!(*err, EvalAltResult::ErrorModuleNotFound(..)));
+    }
+
+    #[test]
+    fn module_escapes_are_denied() {
+        let (tmp, _ctx, modules) = modules("module-escape");
+        assert!(denied(modules.resolve_path(None, "../secret", Position::NONE)));
+        assert!(denied(modules.resolve_path(None, "lib/../../secret", Position::NONE)));
+        let absolute = tmp.0.join("secret");
+        let absolute = absolute.to_str().unwrap();
+        assert!(denied(modules.resolve_path(None, absolute, Position::NONE)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn module_symlink_outside_is_denied() {
+        let (tmp, ctx, modules) = modules("module-symlink");
+        std::os::unix::fs::symlink(&tmp.0, ctx.dirs().lib.join("up")).unwrap();
+        assert!(denied(modules.resolve_path(None, "lib/up/secret", Position::NONE)));
+    }
+}
@@ -0,0 +1,792 @@
+//! Runs games compiled to WebAssembly, written in any language targeting `wasm32-unknown-unknown`.
+//!
+//! The functions of the [`HostApi`] are imported from the `kgames` module, overloads after the
+//! first one get their position as a suffix (`text_2`). Values are passed as plain wasm values:
+//! - `Float` is an `f32`, `Int`, `Bool`, `Key` and `Mouse` are `i32`
+//! - `Vec2`, `Vec3`, `Rect` and `Color` are 2 to 4 `f32`, returned as multiple values
+//! - `String` is a pointer and a length in bytes into the exported `memory`, `Array<String>`
+//!   a pointer to that many pairs of them
+//! - `Texture`, `Sound`, `Font` and the types of the application are handles (`i32`)
+//!   returned by the functions creating them. The same asset always has the same handle,
+//!   values of the application keep theirs until the script calls `drop_handle(handle)`
+//! - `Option<T>` is `-1` for nothing
+//!
+//! Keys, mouse buttons and the number constants of the application are imported globals
+//! with the names of the other backends.
+//! Hooks are exported functions without parameters or results.
+//!
+//! A call may run `max_operations` instructions, the frame budget is checked whenever
+//! the script calls one of the imported functions. Memories can grow up to `max_memory` bytes.
+
+use std::collections::HashMap;
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, SystemTime};
+
+use macroquad::miniquad::TextureId;
+use macroquad::prelude::*;
+use wasmi::core::{TrapCode, ValType, F32};
+use wasmi::{
+    Caller, Config, Extern, FuncType, Global, Instance, Linker, Module, Mutability, Store,
+    StoreLimits, StoreLimitsBuilder, Val,
+};
+
+use crate::assets::AssetId;
+use crate::bindings::{self, Type};
+use crate::ffi;
+use crate::host::{Function, HostApi};
+
+use super::*;
+
+/// Module the host functions and globals are imported from.
+const IMPORT_MODULE: &str = "kgames";
+/// Run after instantiating, for modules built as a library (a "reactor").
+const INITIALIZE: &str = "_initialize";
+
+/// What a handle refers to, so a value returned again keeps its handle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum HandleKey {
+    /// Textures, sounds and fonts of the asset registry
+    Asset(AssetId),
+    /// Builtin textures, which are never freed
+    Texture(TextureId),
+    /// Values of the application, by address
+    Host(usize),
+}
+
+struct HostState {
+    /// Values of the script owned by the host, by handle
+    handles: HashMap<i32, (HandleKey, bindings::Value)>,
+    keys: HashMap<HandleKey, i32>,
+    /// Handles start at 1 and are never reused
+    next_handle: i32,
+    /// `Assets::generation` when the handles of freed assets were last dropped
+    generation: u64,
+    watchdog: Rc<Watchdog>,
+    /// Set by `expose_api`, modules importing functions can only run then
+    api: Option<Rc<HostApi>>,
+    /// Given to the imported functions
+    ctx: Context,
+    /// Size of the memories, see `new_store`
+    limits: StoreLimits,
+}
+
+impl HostState {
+    fn new(ctx: Context, watchdog: Rc<Watchdog>, api: Option<Rc<HostApi>>, max_memory: usize) -> Self {
+        let generation = ctx.assets().generation();
+        Self {
+            limits: memory_limits(max_memory),
+            handles: HashMap::new(),
+            keys: HashMap::new(),
+            next_handle: 1,
+            generation,
+            watchdog,
+            api,
+            ctx,
+        }
+    }
+
+    /// The handle of `value`, the one it already has if it was given to the script before.
+    fn handle(&mut self, value: bindings::Value) -> i32 {
+        use bindings::Value as V;
+
+        let key = match &value {
+            V::Texture(texture) => match self.ctx.assets().texture_id(texture) {
+                Some(id) => HandleKey::Asset(id),
+                None => HandleKey::Texture(texture.raw_miniquad_id()),
+            },
+            V::Sound(sound) => HandleKey::Asset(sound.id()),
+            V::Font(font) => HandleKey::Asset(font.id()),
+            V::Host(value) => HandleKey::Host(value.addr()),
+            // Passed by value, see `write_value`
+            _ => return -1,
+        };
+        if let Some(handle) = self.keys.get(&key) {
+            return *handle;
+        }
+
+        let handle = self.next_handle;
+        self.next_handle += 1;
+        self.keys.insert(key, handle);
+        self.handles.insert(handle, (key, value));
+        handle
+    }
+
+    fn drop_handle(&mut self, handle: i32) {
+        if let Some((key, _)) = self.handles.remove(&handle) {
+            self.keys.remove(&key);
+        }
+    }
+
+    /// Drop the handles of the assets the registry freed, so they are not kept alive by the script.
+    fn drop_freed_assets(&mut self) {
+        let assets = self.ctx.assets();
+        if assets.generation() == self.generation {
+            return;
+        }
+        self.generation = assets.generation();
+        self.handles
+            .retain(|_, (key, _)| !matches!(key, HandleKey::Asset(id) if !assets.contains(*id)));
+        let handles = &self.handles;
+        self.keys.retain(|_, handle| handles.contains_key(handle));
+    }
+}
+
+type WasmResult<T> = Result<T, wasmi::Error>;
+
+/// Limits of the memories of a script, `0` for no limit.
+fn memory_limits(max_memory: usize) -> StoreLimits {
+    match max_memory {
+        0 => StoreLimits::default(),
+        max => StoreLimitsBuilder::new().memory_size(max).build(),
+    }
+}
+
+fn new_store(engine: &wasmi::Engine, state: HostState) -> Store<HostState> {
+    let mut store = Store::new(engine, state);
+    store.limiter(|state| &mut state.limits);
+    store
+}
+
+/// Name of the import of `function`, unique among the overloads.
+fn import_name(api: &HostApi, function: &Function) -> String {
+    let position = api
+        .overloads(&function.name)
+        .position(|f| std::ptr::eq(f, function))
+        .unwrap_or_default();
+    match position {
+        0 => function.name.clone(),
+        n => format!("{}_{}", function.name, n + 1),
+    }
+}
+
+/// The wasm values a value of type `ty` is passed as, `None` if it can not be a result.
+fn wasm_types(ty: Type, result: bool) -> Option<&'static [ValType]> {
+    use ValType::{F32, I32};
+
+    let types: &[ValType] = match ty {
+        Type::Unit => &[],
+        Type::Float => &[F32],
+        Type::Int | Type::Bool | Type::Key | Type::Mouse => &[I32],
+        Type::Texture | Type::Sound | Type::Font | Type::Host(_) => &[I32],
+        Type::String | Type::Strings if result => return None,
+        Type::String | Type::Strings => &[I32, I32],
+        Type::Vec2 => &[F32, F32],
+        Type::Vec3 => &[F32, F32, F32],
+        Type::Rect | Type::Color => &[F32, F32, F32, F32],
+        Type::Optional(inner) => match wasm_types(*inner, result)? {
+            [I32] => &[I32],
+            _ => return None,
+        },
+    };
+    Some(types)
+}
+
+/// Type of the import of `function`, `None` if it can not be called from wasm.
+fn func_type(function: &Function) -> Option<FuncType> {
+    let mut params = vec![];
+    for (_, ty) in &function.params {
+        params.extend_from_slice(wasm_types(*ty, false)?);
+    }
+    let results = match function.returns {
+        Some(ty) => wasm_types(ty, true)?,
+        None => &[],
+    };
+    Some(FuncType::new(params, results.iter().copied()))
+}
+
+fn trap(message: impl Into<String>) -> wasmi::Error {
+    wasmi::Error::new(message)
+}
+
+/// Read a value of type `ty` from the next parameters.
+fn read_value(
+    caller: &Caller<'_, HostState>,
+    params: &mut std::slice::Iter<'_, Val>,
+    ty: Type,
+) -> WasmResult<bindings::Value> {
+    use bindings::Value as V;
+
+    let mut float = || -> WasmResult<f32> {
+        let value = params.next().and_then(Val::f32);
+        value
+            .map(f32::from)
+            .ok_or_else(|| trap("expected an f32 parameter"))
+    };
+    let value = match ty {
+        Type::Unit => V::Unit,
+        Type::Float => V::Float(float()?),
+        Type::Vec2 => V::Vec2(vec2(float()?, float()?)),
+        Type::Vec3 => V::Vec3(vec3(float()?, float()?, float()?)),
+        Type::Rect => V::Rect(Rect::new(float()?, float()?, float()?, float()?)),
+        Type::Color => V::Color(Color::new(float()?, float()?, float()?, float()?)),
+        _ => {
+            let mut int = || -> WasmResult<i32> {
+                let value = params.next().and_then(Val::i32);
+                value.ok_or_else(|| trap("expected an i32 parameter"))
+            };
+            match ty {
+                Type::Int => V::Int(int()?.into()),
+                Type::Bool => V::Bool(int()? != 0),
+                Type::String => {
+                    let (ptr, len) = (int()?, int()?);
+                    V::String(read_string(caller, ptr, len)?)
+                }
+                Type::Strings => {
+                    let (ptr, count) = (int()?, int()?);
+                    let pairs = read_bytes(caller, ptr, count.saturating_mul(8))?;
+                    let strings = pairs
+                        .chunks_exact(8)
+                        .map(|pair| {
+                            let ptr = i32::from_le_bytes([pair[0], pair[1], pair[2], pair[3]]);
+                            let len = i32::from_le_bytes([pair[4], pair[5], pair[6], pair[7]]);
+                            read_string(caller, ptr, len)
+                        })
+                        .collect::<WasmResult<_>>()?;
+                    V::Strings(strings)
+                }
+                Type::Texture | Type::Sound | Type::Font | Type::Host(_) => {
+                    let handle = int()?;
+                    let (_, value) = caller
+                        .data()
+                        .handles
+                        .get(&handle)
+                        .ok_or_else(|| trap(format!("invalid {ty} handle {handle}")))?;
+                    value.clone()
+                }
+                Type::Key => {
+                    let code = int()?;
+                    let key = ffi::KEYS.iter().find(|(_, key)| *key as i32 == code);
+                    V::Key(key.ok_or_else(|| trap(format!("Unknown key {code}")))?.1)
+                }
+                Type::Mouse => {
+                    let code = int()?;
+                    let button = ffi::MOUSE_BUTTONS.iter().find(|(_, b)| *b as i32 == code);
+                    V::Mouse(
+                        button
+                            .ok_or_else(|| trap(format!("Unknown mouse button {code}")))?
+                            .1,
+                    )
+                }
+                Type::Optional(inner) => match params.as_slice().first().and_then(Val::i32) {
+                    Some(-1) => {
+                        params.next();
+                        V::Unit
+                    }
+                    _ => read_value(caller, params, *inner)?,
+                },
+                _ => unreachable!("{ty} is read as floats"),
+            }
+        }
+    };
+    Ok(value)
+}
+
+fn read_bytes(caller: &Caller<'_, HostState>, ptr: i32, len: i32) -> WasmResult<Vec<u8>> {
+    let memory = caller
+        .get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| trap("the module does not export its memory"))?;
+
+    let start = usize::try_from(ptr).map_err(|_| trap("negative pointer"))?;
+    let len = usize::try_from(len).map_err(|_| trap("negative length"))?;
+    memory
+        .data(caller)
+        .get(start..start.saturating_add(len))
+        .map(<[u8]>::to_vec)
+        .ok_or_else(|| trap("out of bounds memory access"))
+}
+
+fn read_string(caller: &Caller<'_, HostState>, ptr: i32, len: i32) -> WasmResult<String> {
+    String::from_utf8(read_bytes(caller, ptr, len)?).map_err(|e| trap(e.to_string()))
+}
+
+/// Write `value` to the results, handles are given to the script.
+fn write_value(caller: &mut Caller<'_, HostState>, value: bindings::Value, results: &mut [Val]) {
+    use bindings::Value as V;
+
+    let floats = |values: &[f32], results: &mut [Val]| {
+        for (result, v) in results.iter_mut().zip(values) {
+            *result = Val::F32(F32::from(*v));
+        }
+    };
+    match value {
+        // Nothing, for optional results
+        V::Unit => results.iter_mut().for_each(|r| *r = Val::I32(-1)),
+        V::Float(v) => floats(&[v], results),
+        V::Int(v) => results[0] = Val::I32(v as i32),
+        V::Bool(v) => results[0] = Val::I32(v.into()),
+        V::Key(v) => results[0] = Val::I32(v as i32),
+        V::Mouse(v) => results[0] = Val::I32(v as i32),
+        V::Vec2(v) => floats(&[v.x, v.y], results),
+        V::Vec3(v) => floats(&[v.x, v.y, v.z], results),
+        V::Rect(v) => floats(&[v.x, v.y, v.w, v.h], results),
+        V::Color(v) => floats(&[v.r, v.g, v.b, v.a], results),
+        // Functions like `get_texture` may be called every frame, without growing the handles
+        v @ (V::Texture(_) | V::Sound(_) | V::Font(_) | V::Host(_)) => {
+            results[0] = Val::I32(caller.data_mut().handle(v))
+        }
+        // Not allowed as results by `wasm_types`
+        V::String(_) | V::Strings(_) => {}
+    }
+}
+
+/// Define the imports every module can use, from the functions and constants of `api`.
+fn define_imports(
+    linker: &mut Linker<HostState>,
+    store: &mut Store<HostState>,
+    api: &HostApi,
+) -> WasmResult<()> {
+    for (index, function) in api.functions().iter().enumerate() {
+        // NOTE: functions returning strings are not available to wasm
+        let Some(ty) = func_type(function) else {
+            continue;
+        };
+        // The closure must be `Send`, so it finds the function in the store by its index
+        linker.func_new(
+            IMPORT_MODULE,
+            &import_name(api, function),
+            ty,
+            move |mut caller, params, results| {
+                let watchdog = &caller.data().watchdog;
+                if watchdog.expired() {
+                    return Err(trap(watchdog.abort_message()));
+                }
+                caller.data_mut().drop_freed_assets();
+                let api = caller
+                    .data()
+                    .api
+                    .clone()
+                    .ok_or_else(|| trap("the API is not exposed"))?;
+                let function = &api.functions()[index];
+
+                let mut params = params.iter();
+                let args = function
+                    .params
+                    .iter()
+                    .map(|(_, ty)| read_value(&caller, &mut params, *ty))
+                    .collect::<WasmResult<_>>()?;
+                let ctx = caller.data().ctx.clone();
+                let value = function.call(&ctx, args).map_err(trap)?;
+                write_value(&mut caller, value, results);
+                Ok(())
+            },
+        )?;
+    }
+
+    linker.func_wrap(
+        IMPORT_MODULE,
+        "drop_handle",
+        |mut caller: Caller<'_, HostState>, handle: i32| caller.data_mut().drop_handle(handle),
+    )?;
+
+    let keys = ffi::KEYS
+        .iter()
+        .map(|(name, key)| ((*name).to_owned(), Val::I32(*key as i32)));
+    let buttons = ffi::MOUSE_BUTTONS
+        .iter()
+        .map(|(name, b)| ((*name).to_owned(), Val::I32(*b as i32)));
+    let constants = api.constants().iter().filter_map(|(name, _, value)| {
+        let value = match *value {
+            bindings::Value::Float(v) => Val::F32(F32::from(v)),
+            bindings::Value::Int(v) => Val::I32(v as i32),
+            bindings::Value::Bool(v) => Val::I32(v.into()),
+            bindings::Value::Key(v) => Val::I32(v as i32),
+            bindings::Value::Mouse(v) => Val::I32(v as i32),
+            _ => return None,
+        };
+        Some((name.clone(), value))
+    });
+    for (name, value) in keys.chain(buttons).chain(constants) {
+        let global = Global::new(&mut *store, value, Mutability::Const);
+        linker.define(IMPORT_MODULE, &name, global)?;
+    }
+    Ok(())
+}
+
+/// Turn a wasm error into a [`ScriptError`].
+fn wasm_error(path: &Path, err: wasmi::Error, max_operations: u64) -> ScriptError {
+    match err.as_trap_code() {
+        Some(TrapCode::OutOfFuel) => ScriptError::new(
+            path,
+            format!("Aborted after {max_operations} instructions, the script may be stuck in an infinite loop"),
+        ),
+        _ => ScriptError::new(path, err),
+    }
+}
+
+pub struct Engine {
+    scripts: Vec<Script>,
+    engine: wasmi::Engine,
+    renamed: Vec<(ScriptId, ScriptId)>,
+    /// Scripts whose top level code ran during the last load
+    loaded: Vec<ScriptId>,
+    /// Set by `expose_api`, modules importing functions can only run then
+    api: Option<Rc<HostApi>>,
+    max_operations: u64,
+    max_memory: usize,
+    watchdog: Rc<Watchdog>,
+    ctx: Context,
+}
+
+impl Engine {
+    pub fn new(ctx: Context) -> Self {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+
+        let mut engine = Self {
+            scripts: vec![],
+            engine: wasmi::Engine::new(&config),
+            renamed: vec![],
+            loaded: vec![],
+            api: None,
+            max_operations: 0,
+            max_memory: 0,
+            watchdog: Rc::new(Watchdog::default()),
+            ctx,
+        };
+        engine.set_limits(&Limits::default());
+        engine
+    }
+
+    fn compile_and_init_script(
+        &self,
+        found: FoundScript,
+        contents: &[u8],
+        modified: SystemTime,
+    ) -> anyhow::Result<Script> {
+        let module =
+            Module::new(&self.engine, contents).map_err(|e| EngineError::Compile(ScriptError::new(&found.path, e)))?;
+
+        let mut script = Script {
+            id: ScriptId::new(&found.path),
+            path: found.path,
+            category: found.category,
+            game_dir: found.game_dir,
+            is_example: found.example,
+            hash: source_hash(contents),
+            modified,
+            store: new_store(
+                &self.engine,
+                HostState::new(
+                    self.ctx.clone(),
+                    self.watchdog.clone(),
+                    self.api.clone(),
+                    self.max_memory,
+                ),
+            ),
+            module,
+            instance: None,
+            max_operations: self.max_operations,
+            max_memory: self.max_memory,
+        };
+
+        // Run Once! (start function, etc)
+        self.ctx.set_running_script(&script);
+        script.run()?;
+
+        Ok(script)
+    }
+
+    /// `missing` holds the scripts whose file is gone, a new file with the same contents is
+    /// the same script renamed.
+    fn load_found_script(
+        &mut self,
+        console: &mut Console,
+        missing: &mut Vec<Script>,
+        found: FoundScript,
+    ) -> anyhow::Result<()> {
+        let path = found.path.clone();
+        let modified = path
+            .metadata()
+            .and_then(|m| m.modified())
+            .map_err(|e| EngineError::io(&path, e))?;
+
+        if let Some(existing) = self.scripts.iter_mut().find(|s| s.path == path) {
+            if existing.modified == modified {
+                return Ok(());
+            }
+            existing.modified = modified;
+
+            let contents = fs::read(&path).map_err(|e| EngineError::io(&path, e))?;
+            existing.hash = source_hash(&contents);
+            existing.module = Module::new(&self.engine, &contents[..])
+                .map_err(|e| EngineError::Compile(ScriptError::new(&path, e)))?;
+
+            // NOTE: the memory layout of the new code is unknown, so its state can not be kept
+            console.log(format!(
+                "Reloading changed script {path:?}, its state is reset"
+            ));
+            existing.reset()?;
+            self.loaded.push(existing.id.clone());
+            self.ctx.set_running_script(&*existing);
+            existing.run()?;
+            return Ok(());
+        }
+
+        let contents = fs::read(&path).map_err(|e| EngineError::io(&path, e))?;
+
+        // Move renamed script
+        let hash = source_hash(&contents);
+        if let Some(i) = missing.iter().position(|s| s.hash == hash) {
+            let mut script = missing.swap_remove(i);
+            console.log(format!("Renamed script {:?} to {path:?}", script.path));
+
+            let old = mem::replace(&mut script.id, ScriptId::new(&path));
+            self.ctx.assets_mut().rename_owner(&old, &script.id);
+            self.renamed.push((old, script.id.clone()));
+            script.path = path;
+            script.modified = modified;
+            script.is_example = found.example;
+            script.category = found.category;
+            script.game_dir = found.game_dir;
+            self.scripts.push(script);
+            return Ok(());
+        }
+
+        console.log(format!("Adding new script {path:?}"));
+        let script = self.compile_and_init_script(found, &contents, modified)?;
+        self.loaded.push(script.id.clone());
+        self.scripts.push(script);
+        Ok(())
+    }
+}
+
+pub struct Script {
+    id: ScriptId,
+    path: PathBuf,
+    category: String,
+    game_dir: Option<PathBuf>,
+    hash: u64,
+    is_example: bool,
+    modified: SystemTime,
+    module: Module,
+    store: Store<HostState>,
+    /// `None` until the module is instantiated by `run`
+    instance: Option<Instance>,
+    max_operations: u64,
+    max_memory: usize,
+}
+
+impl Script {
+    /// Call before running any script code, refills the fuel and starts the watchdog.
+    fn start(&mut self) -> Result<(), ScriptError> {
+        let fuel = match self.max_operations {
+            0 => u64::MAX,
+            n => n,
+        };
+        self.store.data().watchdog.start();
+        self.store
+            .set_fuel(fuel)
+            .map_err(|e| ScriptError::new(&self.path, e))
+    }
+
+    /// Instantiate the module, running its start function and `_initialize`.
+    fn run(&mut self) -> Result<(), EngineError> {
+        let mut linker = Linker::new(self.store.engine());
+        if let Some(api) = self.store.data().api.clone() {
+            define_imports(&mut linker, &mut self.store, &api)
+                .map_err(|e| EngineError::Runtime(ScriptError::new(&self.path, e)))?;
+        }
+
+        self.start().map_err(EngineError::Runtime)?;
+        let instance = linker
+            .instantiate(&mut self.store, &self.module)
+            .and_then(|pre| pre.start(&mut self.store))
+            .map_err(|e| EngineError::Runtime(wasm_error(&self.path, e, self.max_operations)))?;
+        self.instance = Some(instance);
+
+        if self.has_function(INITIALIZE) {
+            self.call(INITIALIZE)?;
+        }
+        Ok(())
+    }
+
+    fn has_function(&self, name: &str) -> bool {
+        self.instance
+            .and_then(|instance| instance.get_typed_func::<(), ()>(&self.store, name).ok())
+            .is_some()
+    }
+
+    /// Call an exported function without parameters or results.
+    fn call(&mut self, name: &str) -> Result<(), EngineError> {
+        let func = self
+            .instance
+            .and_then(|instance| instance.get_typed_func::<(), ()>(&self.store, name).ok())
+            .ok_or_else(|| EngineError::MissingHook {
+                path: self.path.clone(),
+                hook: name.to_owned(),
+            })?;
+
+        self.start().map_err(EngineError::Runtime)?;
+        func.call(&mut self.store, ())
+            .map_err(|e| EngineError::Runtime(wasm_error(&self.path, e, self.max_operations)))
+    }
+}
+
+impl GameScript for Script {
+    fn id<'a>(&'a self) -> &'a ScriptId {
+        &self.id
+    }
+    fn path<'a>(&'a self) -> &'a Path {
+        &self.path
+    }
+    fn name<'a>(&'a self) -> Option<&'a str> {
+        let path = self.game_dir.as_deref().unwrap_or(&self.path);
+        match path.file_name() {
+            Some(path) => path.to_str(),
+            _ => None,
+        }
+    }
+    fn category<'a>(&'a self) -> &'a str {
+        &self.category
+    }
+    fn game_dir<'a>(&'a self) -> Option<&'a Path> {
+        self.game_dir.as_deref()
+    }
+    fn is_example(&self) -> bool {
+        self.is_example
+    }
+    fn reset(&mut self) -> anyhow::Result<()> {
+        // A new store, the old instance and its handles are dropped with it
+        let engine = self.store.engine().clone();
+        let data = self.store.data();
+        let state = HostState::new(
+            data.ctx.clone(),
+            data.watchdog.clone(),
+            data.api.clone(),
+            self.max_memory,
+        );
+        self.store = new_store(&engine, state);
+        self.instance = None;
+        Ok(())
+    }
+    fn populate_scope(&mut self) {
+        // Constants are imported globals, defined by `run`
+    }
+}
+
+impl ScriptEngine for Engine {
+    type Script = Script;
+
+    fn extensions() -> &'static [&'static str] {
+        &["wasm"]
+    }
+    fn context(&self) -> &Context {
+        &self.ctx
+    }
+    fn expose_api(&mut self, api: &HostApi) {
+        // Every script has its own store, the imports are defined when it is instantiated
+        self.api = Some(Rc::new(api.clone()));
+    }
+    fn set_limits(&mut self, limits: &Limits) {
+        // NOTE: one instruction is one operation, there are no strings, arrays or maps to limit
+        self.watchdog
+            .set_budget(Duration::from_millis(limits.frame_budget_ms));
+        self.max_operations = limits.max_operations;
+        self.max_memory = limits.max_memory;
+        for script in &mut self.scripts {
+            script.max_operations = limits.max_operations;
+            script.max_memory = limits.max_memory;
+            script.store.data_mut().limits = memory_limits(limits.max_memory);
+        }
+    }
+    fn write_examples(&mut self, warnings: &mut Vec<String>) -> Result<(), Vec<std::io::Error>> {
+        write_examples(self.ctx.dirs(), Self::extension(), warnings)
+    }
+    fn scripts<'a>(&'a mut self) -> Vec<&'a mut Self::Script> {
+        self.scripts.iter_mut().collect()
+    }
+    fn load_scripts(
+        &mut self,
+        console: &mut Console,
+        errors: &mut ErrorMap,
+        from: &[ScriptDir],
+    ) -> anyhow::Result<()> {
+        let mut result = Ok(());
+
+        for source in from {
+            console.log(format!("==> Loading scripts from {:?}", source.path(self.ctx.dirs())));
+        }
+        let first_error = errors.len();
+        let found = find_scripts(self.ctx.dirs(), from, Self::extensions(), errors);
+        for (path, e) in &errors[first_error..] {
+            console.err(format!("Failed to read dir {path:?}: {}", e.message()));
+            result = Err(anyhow::anyhow!("Failed to read script folders"));
+        }
+
+        // Scripts that are not found anymore were either renamed or deleted
+        let (mut missing, scripts) =
+            mem::take(&mut self.scripts)
+                .into_iter()
+                .partition(|s: &Script| {
+                    let dir = if s.is_example {
+                        ScriptDir::Examples
+                    } else {
+                        ScriptDir::Scripts
+                    };
+                    let searched = from.contains(&dir) || !s.path.is_file();
+                    searched && !found.iter().any(|f| f.path == s.path)
+                });
+        self.scripts = scripts;
+        self.renamed.clear();
+        self.loaded.clear();
+
+        // One broken script does not keep the others from loading
+        for found in found {
+            let path = found.path.clone();
+            if let Err(e) = self.load_found_script(console, &mut missing, found) {
+                errors.push((path, e.into()));
+                result = Err(anyhow::anyhow!("Failed to load scripts"));
+            }
+        }
+
+        for script in missing {
+            console.log(format!("Removing deleted script {:?}", script.path));
+            self.ctx.assets_mut().release(&script.id);
+        }
+
+        result
+    }
+    fn call_function(&mut self, id: &ScriptId, name: impl AsRef<str>) -> anyhow::Result<()> {
+        let script = find_script(&mut self.scripts, id)?;
+        self.ctx.set_running_script(&*script);
+        script.call(name.as_ref()).map_err(Into::into)
+    }
+    fn has_function(&mut self, id: &ScriptId, name: &str) -> bool {
+        find_script(&mut self.scripts, id).is_ok_and(|script| script.has_function(name))
+    }
+    fn restart_script(&mut self, id: &ScriptId) -> anyhow::Result<()> {
+        let script = find_script(&mut self.scripts, id)?;
+        script.reset()?;
+        self.ctx.set_running_script(&*script);
+        script.run().map_err(Into::into)
+    }
+    fn renamed_scripts(&self) -> &[(ScriptId, ScriptId)] {
+        &self.renamed
+    }
+    fn loaded_scripts(&self) -> &[ScriptId] {
+        &self.loaded
+    }
+    fn check_script(&self, path: &Path, source: &[u8]) -> Vec<Diagnostic> {
+        let module = match Module::new(&self.engine, source) {
+            Ok(module) => module,
+            Err(e) => return vec![Diagnostic::error(path, e)],
+        };
+
+        let hooks: Vec<_> = module
+            .exports()
+            .filter(|export| {
+                let func = export.ty().func();
+                func.is_some_and(|f| f.params().is_empty() && f.results().is_empty())
+            })
+            .map(|export| export.name())
+            .collect();
+        check::missing_hooks(path, hooks.iter().copied())
+    }
+    fn api_reference(&self) -> ApiReference {
+        ApiReference::new("wasm", self.api.as_deref().unwrap_or(&HostApi::new()))
+    }
+}
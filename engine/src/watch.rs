@@ -0,0 +1,145 @@
+//! Polling file watcher, used to hot reload scripts while playing.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+use super::*;
+
+pub const DEFAULT_POLL_INTERVAL: Duration = crate::assets::POLL_INTERVAL;
+
+pub struct ScriptWatcher {
+    /// Folders of the watched [`ScriptDir`]s
+    from: Vec<PathBuf>,
+    extensions: &'static [&'static str],
+    interval: Duration,
+    last_poll: Instant,
+    /// Modification time of every file seen during the last poll
+    files: HashMap<PathBuf, SystemTime>,
+}
+
+impl ScriptWatcher {
+    /// Watch all the scripts of engine `E` in `from`, in the folders of `dirs`.
+    pub fn new<E: ScriptEngine>(dirs: &Dirs, from: &[ScriptDir]) -> Self {
+        let mut watcher = Self {
+            from: from.iter().map(|d| d.path(dirs).to_path_buf()).collect(),
+            extensions: E::extensions(),
+            interval: DEFAULT_POLL_INTERVAL,
+            last_poll: Instant::now(),
+            files: HashMap::new(),
+        };
+        watcher.files = watcher.scan();
+        watcher
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    fn scan(&self) -> HashMap<PathBuf, SystemTime> {
+        let mut files = HashMap::new();
+
+        // Every file with one of the extensions, including the ones imported by scripts.
+        for dir in &self.from {
+            walk_dirs(dir, &mut |_, _, entries| {
+                // NOTE: unreadable directories are reported when loading the scripts
+                let Ok(entries) = entries else {
+                    return false;
+                };
+                for entry in entries {
+                    let path = entry.path();
+                    if path.is_dir()
+                        || !path.extension().is_some_and(|e| self.extensions.iter().any(|ext| e == *ext))
+                    {
+                        continue;
+                    }
+                    if let Ok(modified) = path.metadata().and_then(|m| m.modified()) {
+                        files.insert(path, modified);
+                    }
+                }
+                true
+            });
+        }
+        files
+    }
+
+    /// Returns the files that were added, modified or removed since the last poll.
+    /// Only touches the filesystem once every interval, so it can be called every frame.
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        if self.last_poll.elapsed() < self.interval {
+            return vec![];
+        }
+        self.last_poll = Instant::now();
+
+        let files = self.scan();
+
+        let mut changed: Vec<_> = files
+            .iter()
+            .filter(|(path, modified)| self.files.get(*path) != Some(modified))
+            .map(|(path, _)| path.clone())
+            .chain(
+                self.files
+                    .keys()
+                    .filter(|path| !files.contains_key(*path))
+                    .cloned(),
+            )
+            .collect();
+        changed.sort();
+
+        self.files = files;
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::TempDir;
+    use std::fs;
+
+    fn watcher(dir: &Path) -> ScriptWatcher {
+        let mut watcher = ScriptWatcher {
+            from: vec![dir.to_path_buf()],
+            extensions: &["rhai"],
+            interval: Duration::ZERO,
+            last_poll: Instant::now(),
+            files: HashMap::new(),
+        };
+        watcher.files = watcher.scan();
+        watcher
+    }
+
+    #[test]
+    fn changed_files() {
+        let tmp = TempDir::new("watch-changes");
+        fs::write(tmp.0.join("a.rhai"), "").unwrap();
+        let mut watcher = watcher(&tmp.0);
+        assert!(watcher.poll().is_empty());
+
+        fs::write(tmp.0.join("b.rhai"), "").unwrap();
+        fs::write(tmp.0.join("notes.txt"), "").unwrap();
+        fs::remove_file(tmp.0.join("a.rhai")).unwrap();
+        assert_eq!(watcher.poll(), [tmp.0.join("a.rhai"), tmp.0.join("b.rhai")]);
+    }
+
+    #[test]
+    fn hidden_folders_are_skipped() {
+        let tmp = TempDir::new("watch-hidden");
+        fs::create_dir_all(tmp.0.join(".git")).unwrap();
+        fs::write(tmp.0.join(".git/a.rhai"), "").unwrap();
+        assert!(watcher(&tmp.0).files.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_loop_is_walked_once() {
+        let tmp = TempDir::new("watch-loop");
+        fs::create_dir_all(tmp.0.join("games")).unwrap();
+        fs::write(tmp.0.join("games/a.rhai"), "").unwrap();
+        std::os::unix::fs::symlink(&tmp.0, tmp.0.join("games/up")).unwrap();
+
+        let watcher = watcher(&tmp.0);
+        assert_eq!(watcher.files.keys().collect::<Vec<_>>(), [&tmp.0.join("games/a.rhai")]);
+    }
+}
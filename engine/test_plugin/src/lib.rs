@@ -0,0 +1,136 @@
+//! Plugin used by the tests of `engine/src/plugins.rs`.
+//!
+//! It has its own copy of the C interface, like a plugin written in another language.
+
+use std::ffi::{c_char, c_void};
+use std::ptr;
+
+const TYPE_UNIT: u32 = 0;
+const TYPE_INT: u32 = 2;
+const TYPE_STRING: u32 = 4;
+
+#[repr(C)]
+pub struct PluginValue {
+    ty: u32,
+    int: i64,
+    float: f64,
+    string: *const u8,
+    len: usize,
+}
+
+impl PluginValue {
+    fn int(int: i64) -> Self {
+        Self {
+            ty: TYPE_INT,
+            int,
+            float: 0.0,
+            string: ptr::null(),
+            len: 0,
+        }
+    }
+
+    fn string(s: &'static str) -> Self {
+        Self {
+            ty: TYPE_STRING,
+            int: 0,
+            float: 0.0,
+            string: s.as_ptr(),
+            len: s.len(),
+        }
+    }
+}
+
+type PluginFunction =
+    unsafe extern "C" fn(*mut c_void, *const PluginValue, usize, *mut PluginValue) -> bool;
+
+#[repr(C)]
+pub struct PluginRegistrar {
+    abi: u32,
+    register_function: unsafe extern "C" fn(
+        *mut PluginRegistrar,
+        *const c_char,
+        *const u32,
+        usize,
+        u32,
+        PluginFunction,
+        *mut c_void,
+    ) -> bool,
+    register_constant:
+        unsafe extern "C" fn(*mut PluginRegistrar, *const c_char, PluginValue) -> bool,
+    registry: *mut c_void,
+}
+
+#[no_mangle]
+pub extern "C" fn kgames_plugin_abi() -> u32 {
+    if cfg!(feature = "old-abi") {
+        0
+    } else {
+        1
+    }
+}
+
+/// # Safety
+/// Called by kgames with a valid registrar.
+#[no_mangle]
+pub unsafe extern "C" fn kgames_plugin_init(registrar: *mut PluginRegistrar) -> bool {
+    let function = (*registrar).register_function;
+    let constant = (*registrar).register_constant;
+    let data = ptr::null_mut();
+
+    function(
+        registrar,
+        c"add".as_ptr(),
+        [TYPE_INT; 2].as_ptr(),
+        2,
+        TYPE_INT,
+        add,
+        data,
+    ) && function(
+        registrar,
+        c"fail".as_ptr(),
+        ptr::null(),
+        0,
+        TYPE_UNIT,
+        fail,
+        data,
+    ) && function(
+        registrar,
+        c"wrong_type".as_ptr(),
+        ptr::null(),
+        0,
+        TYPE_INT,
+        wrong_type,
+        data,
+    ) && constant(registrar, c"ANSWER".as_ptr(), PluginValue::int(42))
+}
+
+unsafe extern "C" fn add(
+    _: *mut c_void,
+    args: *const PluginValue,
+    _: usize,
+    result: *mut PluginValue,
+) -> bool {
+    *result = PluginValue::int((*args).int + (*args.add(1)).int);
+    true
+}
+
+unsafe extern "C" fn fail(
+    _: *mut c_void,
+    _: *const PluginValue,
+    _: usize,
+    result: *mut PluginValue,
+) -> bool {
+    *result = PluginValue::string("boom");
+    false
+}
+
+/// Declared to return an int
+unsafe extern "C" fn wrong_type(
+    _: *mut c_void,
+    _: *const PluginValue,
+    _: usize,
+    result: *mut PluginValue,
+) -> bool {
+    *result = PluginValue::string("42");
+    true
+}
@@ -0,0 +1,564 @@
+//! Assets loaded by scripts.
+//!
+//! Every asset is owned by the scripts that loaded it, and freed once all of them are stopped.
+//! Assets can also be preloaded: they are queued, then loaded a few at a time between frames,
+//! sounds being decoded in the background over several frames.
+//! Textures and sounds are reloaded in place when their file changes, see [`Assets::poll_changes`].
+
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fmt;
+use std::fs;
+use std::future::Future;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime};
+
+use macroquad::audio::{load_sound_from_bytes, Sound};
+use macroquad::miniquad::TextureId;
+use macroquad::prelude::*;
+
+use crate::ScriptId;
+
+/// How often [`Assets::poll_changes`] looks at the files.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Where the assets of scripts are, implemented by the context of the engine.
+pub trait AssetResolver {
+    /// The canonical path of the asset `path` asked for by the running script,
+    /// or why it can not be loaded, e.g. it is outside of the assets folder.
+    fn asset_path(&self, path: &str) -> Result<PathBuf, String>;
+    /// The script loading assets, which becomes one of their owners.
+    fn running_script(&self) -> Option<ScriptId>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AssetKind {
+    Texture,
+    Sound,
+    Font,
+}
+
+impl AssetKind {
+    /// Guess the kind of an asset from its extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "png" | "jpg" | "jpeg" | "bmp" | "gif" | "tga" | "ico" | "webp" => Some(Self::Texture),
+            "wav" | "ogg" => Some(Self::Sound),
+            "ttf" | "otf" => Some(Self::Font),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for AssetKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Texture => "texture",
+            Self::Sound => "sound",
+            Self::Font => "font",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Identifies a loaded asset. A file loaded again after it was freed gets a new id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AssetId(u64);
+
+/// A sound given to scripts, the sound inside is replaced when its file changes.
+#[derive(Clone)]
+pub struct SoundHandle {
+    id: AssetId,
+    sound: Rc<RefCell<Sound>>,
+}
+
+impl SoundHandle {
+    pub fn get(&self) -> Sound {
+        self.sound.borrow().clone()
+    }
+
+    pub fn id(&self) -> AssetId {
+        self.id
+    }
+}
+
+/// A font given to scripts.
+#[derive(Clone)]
+pub struct FontHandle {
+    id: AssetId,
+    font: Font,
+}
+
+impl FontHandle {
+    pub fn get(&self) -> &Font {
+        &self.font
+    }
+
+    pub fn id(&self) -> AssetId {
+        self.id
+    }
+}
+
+#[derive(Clone)]
+enum Asset {
+    /// Updated in place on reload, GPU textures can change size
+    Texture(Texture2D),
+    Sound(SoundHandle),
+    Font(FontHandle),
+}
+
+impl Asset {
+    fn kind(&self) -> AssetKind {
+        match self {
+            Self::Texture(_) => AssetKind::Texture,
+            Self::Sound(_) => AssetKind::Sound,
+            Self::Font(_) => AssetKind::Font,
+        }
+    }
+}
+
+struct Entry {
+    id: AssetId,
+    asset: Asset,
+    /// Approximate memory used, in bytes
+    size: usize,
+    modified: Option<SystemTime>,
+    owners: BTreeSet<ScriptId>,
+}
+
+/// A loaded asset, as listed by [`Assets::list`].
+#[derive(Debug, Clone)]
+pub struct AssetInfo {
+    pub path: PathBuf,
+    pub kind: AssetKind,
+    pub size: usize,
+    pub owners: Vec<ScriptId>,
+}
+
+/// An asset waiting to be loaded, see [`Assets::preload`].
+struct Pending {
+    /// Canonical path
+    path: PathBuf,
+    kind: AssetKind,
+    owner: Option<ScriptId>,
+}
+
+/// A preloaded sound being decoded, polled by [`Assets::load_pending`] until it is ready.
+struct Decoding {
+    pending: Pending,
+    size: usize,
+    modified: Option<SystemTime>,
+    sound: Pin<Box<dyn Future<Output = Result<Sound, macroquad::Error>>>>,
+}
+
+/// Registry of the assets loaded by scripts, keyed by canonical path.
+#[derive(Default)]
+pub struct Assets {
+    entries: HashMap<PathBuf, Entry>,
+    queue: VecDeque<Pending>,
+    decoding: Vec<Decoding>,
+    /// Assets preloaded since the queue was last empty, to compute the progress
+    queued: usize,
+    last_poll: Option<Instant>,
+    next_id: u64,
+    /// Loaded textures, to find the id of the ones scripts give back
+    textures: HashMap<TextureId, AssetId>,
+    /// Counts the calls to [`Assets::release`] which freed assets
+    generation: u64,
+}
+
+/// A preloaded asset which failed to load, see [`Assets::load_pending`].
+#[derive(Debug)]
+pub struct AssetError {
+    pub path: PathBuf,
+    pub kind: AssetKind,
+    pub message: String,
+}
+
+/// An asset reloaded by [`Assets::poll_changes`].
+pub struct Reloaded {
+    pub path: PathBuf,
+    pub kind: AssetKind,
+    pub result: anyhow::Result<()>,
+}
+
+impl Assets {
+    /// Load a texture relative to the running game of `ctx`, see [`AssetResolver::asset_path`].
+    pub fn texture(&mut self, ctx: &impl AssetResolver, path: &str) -> Result<Texture2D, String> {
+        match self.acquire(ctx, path, AssetKind::Texture)? {
+            Asset::Texture(t) => Ok(t),
+            _ => Err(format!("{path:?} is already loaded as another kind of asset")),
+        }
+    }
+    pub fn sound(&mut self, ctx: &impl AssetResolver, path: &str) -> Result<SoundHandle, String> {
+        match self.acquire(ctx, path, AssetKind::Sound)? {
+            Asset::Sound(s) => Ok(s),
+            _ => Err(format!("{path:?} is already loaded as another kind of asset")),
+        }
+    }
+    pub fn font(&mut self, ctx: &impl AssetResolver, path: &str) -> Result<FontHandle, String> {
+        match self.acquire(ctx, path, AssetKind::Font)? {
+            Asset::Font(f) => Ok(f),
+            _ => Err(format!("{path:?} is already loaded as another kind of asset")),
+        }
+    }
+
+    /// Get an asset from the cache or load it, the running script becomes one of its owners.
+    fn acquire(&mut self, ctx: &impl AssetResolver, path: &str, kind: AssetKind) -> Result<Asset, String> {
+        let full_path = ctx.asset_path(path)?;
+        self.acquire_resolved(&full_path, kind, ctx.running_script())
+            .map_err(|e| format!("Failed to load {kind} {path:?}: {e}"))
+    }
+
+    fn acquire_resolved(
+        &mut self,
+        full_path: &Path,
+        kind: AssetKind,
+        owner: Option<ScriptId>,
+    ) -> anyhow::Result<Asset> {
+        if !self.entries.contains_key(full_path) {
+            let modified = full_path.metadata().and_then(|m| m.modified()).ok();
+            let id = self.new_id();
+            let (asset, size) = load(full_path, kind, id)?;
+            self.insert(full_path, id, asset, size, modified);
+        }
+        Ok(self.own(full_path, owner))
+    }
+
+    fn new_id(&mut self) -> AssetId {
+        let id = AssetId(self.next_id);
+        self.next_id += 1;
+        id
+    }
+
+    fn insert(&mut self, path: &Path, id: AssetId, asset: Asset, size: usize, modified: Option<SystemTime>) {
+        if let Asset::Texture(texture) = &asset {
+            self.textures.insert(texture.raw_miniquad_id(), id);
+        }
+        let entry = Entry {
+            id,
+            asset,
+            size,
+            modified,
+            owners: BTreeSet::new(),
+        };
+        self.entries.insert(path.to_path_buf(), entry);
+    }
+
+    /// Add `owner` to the owners of a loaded asset.
+    fn own(&mut self, path: &Path, owner: Option<ScriptId>) -> Asset {
+        let entry = self.entries.get_mut(path).expect("loaded by the caller");
+        if let Some(owner) = owner {
+            entry.owners.insert(owner);
+        }
+        entry.asset.clone()
+    }
+
+    /// Queue an asset to be loaded by [`Assets::load_pending`], for the running script.
+    /// The kind of asset is guessed from the extension.
+    pub fn preload(&mut self, ctx: &impl AssetResolver, path: &str) -> Result<(), String> {
+        let full_path = ctx.asset_path(path)?;
+        let kind = AssetKind::from_path(&full_path)
+            .ok_or_else(|| format!("Can not preload {path:?}, unknown type of asset"))?;
+        let owner = ctx.running_script();
+
+        if let Some(entry) = self.entries.get_mut(&full_path) {
+            if let Some(owner) = owner {
+                entry.owners.insert(owner);
+            }
+            return Ok(());
+        }
+        let decoding = self.decoding.iter().map(|d| &d.pending);
+        if self.queue.iter().chain(decoding).any(|p| p.path == full_path && p.owner == owner) {
+            return Ok(());
+        }
+
+        self.queue.push_back(Pending {
+            path: full_path,
+            kind,
+            owner,
+        });
+        self.queued += 1;
+        Ok(())
+    }
+
+    /// Load queued assets while `budget` is not spent, and check on the sounds being decoded.
+    /// An asset is loaded at once, so a large texture can still take longer than the budget.
+    /// Returns the assets that failed to load.
+    pub fn load_pending(&mut self, budget: Duration) -> Vec<AssetError> {
+        let start = Instant::now();
+        let mut errors = vec![];
+
+        while start.elapsed() < budget {
+            let Some(pending) = self.queue.pop_front() else {
+                break;
+            };
+            let Pending { path, kind, owner } = pending;
+            let result = match kind {
+                AssetKind::Sound if !self.entries.contains_key(&path) => self.decode(&path, owner),
+                _ => self.acquire_resolved(&path, kind, owner).map(drop),
+            };
+            if let Err(e) = result {
+                errors.push(AssetError {
+                    path,
+                    kind,
+                    message: format!("{e:#}"),
+                });
+            }
+        }
+        self.poll_decoding(&mut errors);
+
+        if !self.is_loading() {
+            self.queued = 0;
+        }
+        errors
+    }
+
+    /// Start decoding a preloaded sound, finished by [`Assets::poll_decoding`].
+    fn decode(&mut self, path: &Path, owner: Option<ScriptId>) -> anyhow::Result<()> {
+        let modified = path.metadata().and_then(|m| m.modified()).ok();
+        let bytes = fs::read(path)?;
+        self.decoding.push(Decoding {
+            pending: Pending {
+                path: path.to_path_buf(),
+                kind: AssetKind::Sound,
+                owner,
+            },
+            size: bytes.len(),
+            modified,
+            sound: Box::pin(async move { load_sound_from_bytes(&bytes).await }),
+        });
+        Ok(())
+    }
+
+    /// Add the sounds done decoding to the registry, without waiting for the others.
+    fn poll_decoding(&mut self, errors: &mut Vec<AssetError>) {
+        let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+
+        for mut decoding in mem::take(&mut self.decoding) {
+            let Poll::Ready(result) = decoding.sound.as_mut().poll(&mut cx) else {
+                self.decoding.push(decoding);
+                continue;
+            };
+            let Pending { path, kind, owner } = decoding.pending;
+            match result {
+                Ok(sound) => {
+                    // Unless a script loaded it meanwhile
+                    if !self.entries.contains_key(&path) {
+                        let id = self.new_id();
+                        let sound = Rc::new(RefCell::new(sound));
+                        let asset = Asset::Sound(SoundHandle { id, sound });
+                        self.insert(&path, id, asset, decoding.size, decoding.modified);
+                    }
+                    self.own(&path, owner);
+                }
+                Err(e) => errors.push(AssetError {
+                    path,
+                    kind,
+                    message: e.to_string(),
+                }),
+            }
+        }
+    }
+
+    /// Whether preloaded assets are still waiting to be loaded.
+    pub fn is_loading(&self) -> bool {
+        !self.queue.is_empty() || !self.decoding.is_empty()
+    }
+
+    /// Part of the preloaded assets already loaded, from 0 to 1. 1 when nothing is queued.
+    pub fn loading_progress(&self) -> f32 {
+        if self.queued == 0 {
+            return 1.0;
+        }
+        let waiting = self.queue.len() + self.decoding.len();
+        (self.queued - waiting) as f32 / self.queued as f32
+    }
+
+    /// Give up the assets of `owner`, freeing the ones no other script uses.
+    /// Returns how many assets were freed.
+    pub fn release(&mut self, owner: &ScriptId) -> usize {
+        let waiting = self.queue.len() + self.decoding.len();
+        self.queue.retain(|p| p.owner.as_ref() != Some(owner));
+        self.decoding.retain(|d| d.pending.owner.as_ref() != Some(owner));
+        self.queued -= waiting - self.queue.len() - self.decoding.len();
+        if !self.is_loading() {
+            self.queued = 0;
+        }
+
+        let before = self.entries.len();
+        let textures = &mut self.textures;
+        self.entries.retain(|_, entry| {
+            entry.owners.remove(owner);
+            let freed = entry.owners.is_empty();
+            if let (true, Asset::Texture(texture)) = (freed, &entry.asset) {
+                textures.remove(&texture.raw_miniquad_id());
+            }
+            !freed
+        });
+
+        let freed = before - self.entries.len();
+        if freed > 0 {
+            self.generation += 1;
+        }
+        freed
+    }
+
+    /// The id of a texture from the registry, `None` for other textures like the builtin ones.
+    pub fn texture_id(&self, texture: &Texture2D) -> Option<AssetId> {
+        self.textures.get(&texture.raw_miniquad_id()).copied()
+    }
+
+    /// Whether the asset `id` is still loaded.
+    pub fn contains(&self, id: AssetId) -> bool {
+        self.entries.values().any(|e| e.id == id)
+    }
+
+    /// Changes whenever assets are freed, so holders of ids know when to check them again
+    /// with [`Assets::contains`].
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Move the assets of a renamed script to its new id.
+    pub fn rename_owner(&mut self, old: &ScriptId, new: &ScriptId) {
+        for entry in self.entries.values_mut() {
+            if entry.owners.remove(old) {
+                entry.owners.insert(new.clone());
+            }
+        }
+        let decoding = self.decoding.iter_mut().map(|d| &mut d.pending);
+        for pending in self.queue.iter_mut().chain(decoding) {
+            if pending.owner.as_ref() == Some(old) {
+                pending.owner = Some(new.clone());
+            }
+        }
+    }
+
+    /// All loaded assets, sorted by path.
+    pub fn list(&self) -> Vec<AssetInfo> {
+        let mut list: Vec<_> = self
+            .entries
+            .iter()
+            .map(|(path, entry)| AssetInfo {
+                path: path.clone(),
+                kind: entry.asset.kind(),
+                size: entry.size,
+                owners: entry.owners.iter().cloned().collect(),
+            })
+            .collect();
+        list.sort_by(|a, b| a.path.cmp(&b.path));
+        list
+    }
+
+    /// Reload the textures and sounds whose file changed, keeping the handles scripts hold.
+    /// Only touches the filesystem once every [`POLL_INTERVAL`], so it can be called every frame.
+    pub fn poll_changes(&mut self) -> Vec<Reloaded> {
+        if self.last_poll.is_some_and(|t| t.elapsed() < POLL_INTERVAL) {
+            return vec![];
+        }
+        self.last_poll = Some(Instant::now());
+
+        let mut reloaded = vec![];
+        for (path, entry) in &mut self.entries {
+            // NOTE: deleted files keep the last version
+            let Ok(modified) = path.metadata().and_then(|m| m.modified()) else {
+                continue;
+            };
+            if entry.modified == Some(modified) || matches!(entry.asset, Asset::Font(_)) {
+                continue;
+            }
+            entry.modified = Some(modified);
+
+            reloaded.push(Reloaded {
+                path: path.clone(),
+                kind: entry.asset.kind(),
+                result: reload(path, entry),
+            });
+        }
+        reloaded.sort_by(|a, b| a.path.cmp(&b.path));
+        reloaded
+    }
+
+    /// Approximate memory used by all the assets, in bytes.
+    pub fn total_size(&self) -> usize {
+        self.entries.values().map(|e| e.size).sum()
+    }
+}
+
+fn load(path: &Path, kind: AssetKind, id: AssetId) -> anyhow::Result<(Asset, usize)> {
+    let bytes = fs::read(path)?;
+
+    let loaded = match kind {
+        AssetKind::Texture => {
+            let image = Image::from_file_with_format(&bytes, None)?;
+            let size = image.bytes.len();
+            (Asset::Texture(Texture2D::from_image(&image)), size)
+        }
+        AssetKind::Sound => {
+            let sound = futures::executor::block_on(load_sound_from_bytes(&bytes))?;
+            let sound = Rc::new(RefCell::new(sound));
+            (Asset::Sound(SoundHandle { id, sound }), bytes.len())
+        }
+        AssetKind::Font => {
+            let font = load_ttf_font_from_bytes(&bytes)?;
+            (Asset::Font(FontHandle { id, font }), bytes.len())
+        }
+    };
+    Ok(loaded)
+}
+
+/// Load the file of an entry again, into the same texture or sound handle.
+fn reload(path: &Path, entry: &mut Entry) -> anyhow::Result<()> {
+    let bytes = fs::read(path)?;
+
+    match &entry.asset {
+        Asset::Texture(texture) => {
+            let image = Image::from_file_with_format(&bytes, None)?;
+            let (width, height) = (image.width as u32, image.height as u32);
+            if texture.width() as u32 == width && texture.height() as u32 == height {
+                texture.update(&image);
+            } else {
+                // SAFETY: only called from the main thread, between frames
+                let gl = unsafe { get_internal_gl() };
+                gl.quad_context.texture_resize(
+                    texture.raw_miniquad_id(),
+                    width,
+                    height,
+                    Some(&image.bytes),
+                );
+            }
+            entry.size = image.bytes.len();
+        }
+        Asset::Sound(handle) => {
+            let sound = futures::executor::block_on(load_sound_from_bytes(&bytes))?;
+            *handle.sound.borrow_mut() = sound;
+            entry.size = bytes.len();
+        }
+        Asset::Font(_) => anyhow::bail!("Fonts can not be reloaded"),
+    }
+    Ok(())
+}
+
+/// Format a size in bytes for people.
+pub fn human_size(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
@@ -0,0 +1,210 @@
+//! Types of the values passed between scripts and the functions they call, shared by every backend.
+
+use std::any::Any;
+use std::fmt::{self, Display};
+use std::rc::Rc;
+
+use macroquad::prelude::*;
+
+use crate::assets::{FontHandle, SoundHandle};
+
+/// Type of a parameter or return value, as scripts see it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    Unit,
+    Float,
+    Int,
+    Bool,
+    String,
+    Strings,
+    Vec2,
+    Vec3,
+    Rect,
+    Color,
+    Texture,
+    Sound,
+    Font,
+    Key,
+    Mouse,
+    /// Nothing (`()` or `nil`) or a value
+    Optional(&'static Type),
+    /// A [`HostType`], by name
+    Host(&'static str),
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unit => write!(f, "()"),
+            Self::Float => write!(f, "Float"),
+            Self::Int => write!(f, "Int"),
+            Self::Bool => write!(f, "Bool"),
+            Self::String => write!(f, "String"),
+            Self::Strings => write!(f, "Array<String>"),
+            Self::Vec2 => write!(f, "Vec2"),
+            Self::Vec3 => write!(f, "Vec3"),
+            Self::Rect => write!(f, "Rect"),
+            Self::Color => write!(f, "Color"),
+            Self::Texture => write!(f, "Texture"),
+            Self::Sound => write!(f, "Sound"),
+            Self::Font => write!(f, "Font"),
+            Self::Key => write!(f, "Key"),
+            Self::Mouse => write!(f, "Mouse"),
+            Self::Optional(ty) => write!(f, "Option<{ty}>"),
+            Self::Host(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// Argument or result of a binding, converted from and to the values of each language.
+#[derive(Clone)]
+pub enum Value {
+    Unit,
+    Float(f32),
+    Int(i64),
+    Bool(bool),
+    String(String),
+    Strings(Vec<String>),
+    Vec2(Vec2),
+    Vec3(Vec3),
+    Rect(Rect),
+    Color(Color),
+    Texture(Texture2D),
+    Sound(SoundHandle),
+    Font(FontHandle),
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Host(HostValue),
+}
+
+/// Rust types that can be parameters or results of a binding.
+pub trait ApiType: Sized {
+    const TYPE: Type;
+    fn from_value(value: Value) -> Option<Self>;
+    fn into_value(self) -> Value;
+}
+
+macro_rules! api_types {
+    ($($ty:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl ApiType for $ty {
+                const TYPE: Type = Type::$variant;
+                fn from_value(value: Value) -> Option<Self> {
+                    match value {
+                        Value::$variant(v) => Some(v),
+                        _ => None,
+                    }
+                }
+                fn into_value(self) -> Value {
+                    Value::$variant(self)
+                }
+            }
+        )*
+    };
+}
+
+api_types! {
+    i64 => Int,
+    bool => Bool,
+    String => String,
+    Vec<String> => Strings,
+    Vec2 => Vec2,
+    Vec3 => Vec3,
+    Rect => Rect,
+    Color => Color,
+    Texture2D => Texture,
+    SoundHandle => Sound,
+    FontHandle => Font,
+    KeyCode => Key,
+    MouseButton => Mouse,
+}
+
+impl ApiType for () {
+    const TYPE: Type = Type::Unit;
+    fn from_value(value: Value) -> Option<Self> {
+        matches!(value, Value::Unit).then_some(())
+    }
+    fn into_value(self) -> Value {
+        Value::Unit
+    }
+}
+
+/// Integers are accepted where a float is expected, `circle(10, 10, 5, RED)` is fine.
+impl ApiType for f32 {
+    const TYPE: Type = Type::Float;
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Float(v) => Some(v),
+            Value::Int(v) => Some(v as f32),
+            _ => None,
+        }
+    }
+    fn into_value(self) -> Value {
+        Value::Float(self)
+    }
+}
+
+impl ApiType for i32 {
+    const TYPE: Type = Type::Int;
+    fn from_value(value: Value) -> Option<Self> {
+        i64::from_value(value).and_then(|v| v.try_into().ok())
+    }
+    fn into_value(self) -> Value {
+        Value::Int(self.into())
+    }
+}
+
+impl<T: ApiType> ApiType for Option<T> {
+    const TYPE: Type = Type::Optional(&T::TYPE);
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Unit => Some(None),
+            value => T::from_value(value).map(Some),
+        }
+    }
+    fn into_value(self) -> Value {
+        self.map_or(Value::Unit, T::into_value)
+    }
+}
+
+/// Types the embedder gives to scripts, which only see them as opaque values.
+/// They are passed to and returned from functions, and show up in the API reference by [`HostType::NAME`].
+pub trait HostType: Clone + 'static {
+    const NAME: &'static str;
+}
+
+impl<T: HostType> ApiType for T {
+    const TYPE: Type = Type::Host(T::NAME);
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Host(v) => v.value.downcast_ref::<T>().cloned(),
+            _ => None,
+        }
+    }
+    fn into_value(self) -> Value {
+        Value::Host(HostValue {
+            name: T::NAME,
+            value: Rc::new(self),
+        })
+    }
+}
+
+/// A value of a [`HostType`], as stored in scripts.
+#[derive(Clone)]
+pub struct HostValue {
+    pub name: &'static str,
+    value: Rc<dyn Any>,
+}
+
+impl HostValue {
+    /// Address of the value, the same for clones of this `HostValue` but not for copies of the value.
+    pub fn addr(&self) -> usize {
+        Rc::as_ptr(&self.value).cast::<()>() as usize
+    }
+}
+
+impl fmt::Debug for HostValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
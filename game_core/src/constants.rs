@@ -1,79 +1,9 @@
-use std::{cell::OnceCell, fmt::Display, path::PathBuf};
+//! Names of the colors, keys and mouse buttons given to scripts.
 
 use macroquad::prelude::*;
 
 use KeyCode::*;
 
-use crate::texture::{asset_store, asset_store_mut, AssetStore};
-use engine::dirs;
-
-// Macros
-/// Macro for adding getters/setters to exposed types.
-/// Example:
-/// ```rust
-/// reg_type! {
-///     self.engine => {
-///         Vec2 as "Vec2" = x, y;
-///         Vec3 as "Vec3" = x, y, z;
-///     }
-///}
-/// ```
-#[macro_export]
-macro_rules! reg_type {
-    (
-        $engine: expr => {
-            $(
-                $name:ty as $exposed_name:literal $(=
-                    $($field:ident),*)?;
-            )*
-        }
-    ) => {
-        $(
-            $(
-                $engine.register_type_with_name::<$name>($exposed_name);
-
-                // Register get/set for each field or method
-                $(
-                    $engine.register_get_set(
-                        stringify!($field),
-                        |_self: &mut $name| _self.$field,
-                        |_self: &mut $name, new| _self.$field = new
-                    );
-                )*
-
-            )*
-        )?
-    };
-    // Getters via methods
-    (
-        $engine: expr => {
-            $(
-                $name:ty = $($method:ident()),*;
-            )*
-        }
-    ) => {
-       $(
-           $(
-                $engine.register_get(
-                    stringify!($field),
-                    |_self: &mut $name| _self.$method(),
-                );
-           )*
-        )*
-    };
-}
-
-/*
-reg_type! {
-    self.engine => {
-        Vec2 as "Vec2" = r, g, b, a
-
-    }
-}
-*/
-
-// Constants
-
 pub const COLORS: [(&'static str, Color); 26] = [
     ("LIGHTGRAY", LIGHTGRAY),
     ("GRAY", GRAY),
@@ -234,40 +164,3 @@ pub const MOUSE_BUTTONS: [(&'static str, MouseButton); 4] = [
     ("MOUSE_MIDDLE", MouseButton::Middle),
     ("MOUSE_UNKNOWN", MouseButton::Unknown),
 ];
-
-use engine::external_error;
-use engine::scripting;
-
-/// Sync version of load_texture compatible with rhai
-pub fn load_texture_sync(path: &str) -> scripting::Result<&Texture2D> {
-    let complete_path = dirs().assets.join(path);
-
-    let path_str = complete_path
-        .to_str()
-        .ok_or(format!(
-            "Failed to convert path {path:?} to string (Maybe invalid UTF-8?)"
-        ))
-        .map_err(external_error)?;
-
-    let texture = futures::executor::block_on(load_texture(path_str)).map_err(external_error)?;
-
-    asset_store_mut()
-        .user_textures
-        .insert(path_str.to_string().into_boxed_str(), texture);
-
-    // NOTE: it is inserted, so we know it exists
-    Ok(unsafe { asset_store_mut().user_textures.get(path).unwrap_unchecked() })
-}
-
-/// Get stored texture (from engine)
-pub fn load_texture_stored(name: &str) -> scripting::Result<&Texture2D> {
-    asset_store()
-        .get_texture(name)
-        .ok_or(external_error(format!("Texture not found: '{name}'")))
-}
-
-pub fn draw_texture_stored(name: &str, x: f32, y: f32, tint: Color) -> scripting::Result<()> {
-    let tex = load_texture_stored(name)?;
-    draw_texture(tex, x, y, tint);
-    Ok(())
-}
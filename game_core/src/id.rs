@@ -0,0 +1,21 @@
+use std::fmt::{self, Display};
+use std::path::{Path, PathBuf};
+
+/// Handle to a script that stays valid across reloads, based on its canonical path.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ScriptId(PathBuf);
+impl ScriptId {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        // NOTE: deleted files can not be canonicalized
+        Self(std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()))
+    }
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+impl Display for ScriptId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.display())
+    }
+}
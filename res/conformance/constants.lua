@@ -0,0 +1,12 @@
+-- Colors, keys and mouse buttons are constants
+function test()
+    if WHITE.r ~= 1.0 or WHITE.a ~= 1.0 then
+        error("WHITE should be opaque white")
+    end
+    if KEY_A ~= KEY_A or KEY_A == KEY_B then
+        error("keys should be distinct")
+    end
+    if MOUSE_LEFT ~= MOUSE_LEFT or MOUSE_LEFT == MOUSE_RIGHT then
+        error("mouse buttons should be distinct")
+    end
+end
@@ -0,0 +1,4 @@
+-- expect: boom
+function test()
+    error("boom")
+end
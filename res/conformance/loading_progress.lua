@@ -0,0 +1,6 @@
+-- Nothing is being loaded
+function test()
+    if loading_progress() ~= 1.0 then
+        error("loading_progress() should be 1.0 when nothing is queued")
+    end
+end
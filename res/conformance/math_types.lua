@@ -0,0 +1,21 @@
+-- vec2, vec3, rect and color have readable fields
+function test()
+    local v = vec2(1.0, 2.0)
+    if v.x ~= 1.0 or v.y ~= 2.0 then
+        error("vec2 fields")
+    end
+    local w = vec3(1.0, 2.0, 3.0)
+    if w.z ~= 3.0 then
+        error("vec3 fields")
+    end
+    local c = color(0.5, 0.25, 0.0, 1.0)
+    if c.r ~= 0.5 or c.g ~= 0.25 then
+        error("color fields")
+    end
+    local a = rect(0.0, 0.0, 10.0, 10.0)
+    local b = rect(5.0, 5.0, 10.0, 10.0)
+    local far = rect(50.0, 50.0, 1.0, 1.0)
+    if a.w ~= 10.0 or not overlaps(a, b) or overlaps(a, far) then
+        error("rect overlaps")
+    end
+end
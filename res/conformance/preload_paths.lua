@@ -0,0 +1,6 @@
+-- Assets outside of the data folder can not be loaded
+function test()
+    if pcall(preload, "../../outside.png") then
+        error("preload should refuse paths outside of the data folder")
+    end
+end
@@ -0,0 +1,42 @@
+state = {
+    pos = vec2(100.0, 100.0),
+    vel = vec2(200.0, 200.0),
+    yes = get_texture("yes"),
+    no = get_texture("no"),
+}
+
+local SIZE = vec2(32.0, 32.0)
+
+local function draw_bouncy(s)
+    local tex
+    if s.vel.x < 0.0 then
+        tex = s.yes
+    else
+        tex = s.no
+    end
+    texture(tex, s.pos.x, s.pos.y, WHITE)
+end
+
+function update()
+    local width = screen_width()
+    local height = screen_height()
+
+    local dt = deltatime()
+    state.pos.x += state.vel.x * dt
+    state.pos.y += state.vel.y * dt
+
+    if state.pos.x <= 0.0 or state.pos.x + SIZE.x >= width then
+        state.pos.x = math.min(math.max(state.pos.x, 0.0), width)
+        state.vel.x *= -1.0
+    end
+
+    if state.pos.y <= 0.0 or state.pos.y + SIZE.y >= height then
+        state.pos.y = math.min(math.max(state.pos.y, 0.0), height)
+        state.vel.y *= -1.0
+    end
+end
+
+function draw()
+    clear(WIN98_BG)
+    draw_bouncy(state)
+end
@@ -0,0 +1,31 @@
+-- Created with `kgames new`.
+-- update() is called every frame before draw(), use it for game logic.
+-- draw() is called every frame after update(), use it for drawing.
+
+state = {
+    pos = vec2(100.0, 100.0),
+    speed = 200.0,
+}
+
+function update()
+    local dt = deltatime()
+
+    if key_down(KEY_LEFT) then
+        state.pos.x -= state.speed * dt
+    end
+    if key_down(KEY_RIGHT) then
+        state.pos.x += state.speed * dt
+    end
+    if key_down(KEY_UP) then
+        state.pos.y -= state.speed * dt
+    end
+    if key_down(KEY_DOWN) then
+        state.pos.y += state.speed * dt
+    end
+end
+
+function draw()
+    clear(WIN98_BG)
+    text("Use the arrow keys to move", 10.0, 30.0, 30.0, WHITE)
+    rectangle(state.pos.x, state.pos.y, 32.0, 32.0, YELLOW)
+end
@@ -0,0 +1,200 @@
+//! Command line interface
+//!
+//! Every subcommand except `run` works without opening a window,
+//! so they can be used from CI.
+
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+use engine::check::{self, check_scripts};
+use engine::*;
+
+use crate::CONFIG_FILE;
+
+const SCRIPT_DIRS: &[ScriptDir] = &[ScriptDir::Scripts, ScriptDir::Examples];
+
+static RHAI_TEMPLATE: &str = include_str!("../res/templates/game.rhai");
+static LUA_TEMPLATE: &str = include_str!("../res/templates/game.lua");
+
+#[derive(Parser, Debug)]
+#[command(name = "kgames", version, about = "A scripting engine for making your own games")]
+pub struct Cli {
+    /// Folder containing the scripts, examples and assets
+    #[arg(long, global = true, value_name = "DIR")]
+    pub data_dir: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Start a script directly, skipping the menu
+    Run {
+        /// Name of the script, e.g. `bouncing.rhai`
+        script: String,
+        /// Start in fullscreen
+        #[arg(long)]
+        fullscreen: bool,
+        /// Window size, e.g. `1280x720`
+        #[arg(long, value_name = "WxH", value_parser = parse_size)]
+        size: Option<(i32, i32)>,
+    },
+    /// Print all discovered scripts with their status
+    List,
+    /// Compile all scripts, exits with a non-zero code on errors
+    Check {
+        /// Print the diagnostics as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Create a new script from a template
+    New {
+        /// Name of the new script, without extension
+        name: String,
+        /// Language of the script, e.g. `lua`, defaults to the first enabled backend
+        #[arg(long, value_name = "EXT")]
+        lang: Option<String>,
+    },
+    /// Print the scripting API as Markdown
+    Api {
+        /// Print the API as JSON instead
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+fn parse_size(s: &str) -> Result<(i32, i32), String> {
+    let (w, h) = s
+        .split_once(['x', 'X'])
+        .ok_or(format!("expected WIDTHxHEIGHT, got '{s}'"))?;
+
+    let parse = |n: &str| match n.trim().parse::<i32>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!("invalid size '{n}' in '{s}'")),
+    };
+
+    Ok((parse(w)?, parse(h)?))
+}
+
+/// An engine with the API, the functions of the plugins and the limits of the config,
+/// like the one of `run`. Plugins and configs failing to load are printed.
+fn headless_engine(ctx: Context) -> impl ScriptEngine {
+    let mut api = HostApi::new();
+    let mut errors = vec![];
+    plugins::load_plugins(ctx.dirs(), &mut api, &mut errors);
+    for (path, e) in errors {
+        eprintln!("warning: {}: {}", path.display(), e.message());
+    }
+
+    let config_path = ctx.dirs().root.join(CONFIG_FILE);
+    let mut limits = Limits::default();
+    if config_path.is_file() {
+        match ::config::Config::read(&config_path) {
+            Ok(config) => limits = config.limits,
+            Err(e) => eprintln!("warning: failed to read config {}: {e}", config_path.display()),
+        }
+    }
+
+    let mut engine = Engine::new(ctx);
+    engine.expose_api(&api);
+    engine.set_limits(&limits);
+    engine
+}
+
+pub fn list(ctx: Context) -> ExitCode {
+    let engine = headless_engine(ctx);
+
+    for report in check_scripts(&engine, SCRIPT_DIRS) {
+        let kind = if report.example { "example" } else { "script" };
+        let status = if report.is_ok() { "ok" } else { "error" };
+        println!("{status:<5} {kind:<7} {}", report.path.display());
+    }
+
+    ExitCode::SUCCESS
+}
+
+pub fn check(ctx: Context, json: bool) -> ExitCode {
+    let engine = headless_engine(ctx);
+    let reports = check_scripts(&engine, SCRIPT_DIRS);
+
+    let diagnostics = reports.iter().flat_map(|r| &r.diagnostics);
+    let failed = diagnostics.clone().filter(|d| d.is_error()).count();
+
+    if json {
+        println!("{}", check::to_json(&reports));
+    } else {
+        for diagnostic in diagnostics {
+            eprintln!("{diagnostic}");
+        }
+        println!("checked {} scripts, {failed} errors", reports.len());
+    }
+
+    if failed > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+pub fn new(dirs: &Dirs, name: &str, lang: Option<&str>) -> ExitCode {
+    let mut components = Path::new(name).components();
+    let (Some(Component::Normal(_)), None) = (components.next(), components.next()) else {
+        eprintln!("error: invalid name '{name}', it can not contain path separators or '..'");
+        return ExitCode::FAILURE;
+    };
+
+    let ext = lang.unwrap_or(Engine::extension());
+    let template = match ext {
+        "rhai" if Engine::extensions().contains(&ext) => RHAI_TEMPLATE,
+        "lua" if Engine::extensions().contains(&ext) => LUA_TEMPLATE,
+        "wasm" => {
+            eprintln!("error: wasm games are compiled, see the README for the functions they can import");
+            return ExitCode::FAILURE;
+        }
+        _ => {
+            let enabled = Engine::extensions().join(", ");
+            eprintln!("error: no scripting backend for '{ext}', this build supports {enabled}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if let Err(e) = dirs.create() {
+        eprintln!("error: failed to create required directories: {e}");
+        return ExitCode::FAILURE;
+    }
+
+    let path = dirs
+        .scripts
+        .join(name)
+        .with_extension(ext);
+
+    if path.exists() {
+        eprintln!("error: {} already exists", path.display());
+        return ExitCode::FAILURE;
+    }
+
+    match fs::write(&path, template) {
+        Ok(()) => {
+            println!("created {}", path.display());
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("error: failed to write {}: {e}", path.display());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+pub fn api(ctx: Context, json: bool) -> ExitCode {
+    let api = headless_engine(ctx).api_reference();
+
+    if json {
+        println!("{}", api.to_json());
+    } else {
+        println!("{}", api.to_markdown());
+    }
+    ExitCode::SUCCESS
+}
@@ -1,5 +1,6 @@
 //! Cross platform code
 
+use std::env;
 use std::ffi::OsStr;
 use std::path::Path;
 use std::process::{Command, ExitStatus};
@@ -21,6 +22,20 @@ pub fn open_path(p: impl AsRef<Path>) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Open a file in `$EDITOR`, or the default application if it is not set.
+pub fn open_in_editor(p: impl AsRef<Path>) -> anyhow::Result<()> {
+    let editor = env::var("EDITOR").unwrap_or_default();
+    let mut editor = editor.split_whitespace();
+
+    match editor.next() {
+        Some(cmd) => {
+            Command::new(cmd).args(editor).arg(p.as_ref()).spawn()?;
+            Ok(())
+        }
+        None => open_path(p),
+    }
+}
+
 /// Shortcut to run a command
 #[inline]
 #[allow(unused)]
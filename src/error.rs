@@ -1,13 +1,42 @@
-use engine::ScriptDir;
+use engine::{EngineError, ErrorMap};
 use macroquad::prelude::*;
-use std::path::PathBuf;
+use miniquad::window::clipboard_set;
+use std::fs;
+use std::path::Path;
 
+use crate::cross;
+use crate::ui::UI;
 use crate::{menu::Console, ui::Logger};
-use engine::ScriptEngine;
+
+/// Lines of source shown before and after the line with the error
+const EXCERPT_CONTEXT: usize = 2;
+/// Pixels scrolled per mouse wheel step
+const SCROLL_STEP: f32 = 60.0;
+/// Pixels scrolled per second while holding an arrow key
+const SCROLL_SPEED: f32 = 600.0;
+const FOOTER_HEIGHT: f32 = 60.0;
+
+/// What the user chose to do on the error page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorAction {
+    Close,
+    /// Continue running the paused script
+    Retry,
+    /// Reload the scripts and restart the paused script
+    Reload,
+    /// Reload the scripts, the running script keeps its state
+    Refresh,
+    BackToMenu,
+}
 
 pub struct ErrorPage {
     context: String,
-    errors: Vec<(PathBuf, anyhow::Error)>,
+    errors: ErrorMap,
+    /// Source lines around each error, with their line numbers
+    excerpts: Vec<Vec<(usize, String)>>,
+    scroll: f32,
+    /// Whether a running script was paused by the error
+    runtime: bool,
 }
 
 fn draw_centered(text: &str, y: f32, size: f32, color: Color) {
@@ -44,89 +73,256 @@ fn draw_clamped(text: &str, x: f32, y: f32, size: f32, color: Color) -> usize {
     num_rows
 }
 
+/// Tabs are not drawn by macroquad, so replace them with spaces
+fn untabify(line: &str) -> String {
+    line.replace('\t', "    ")
+}
+
+/// Read the lines around the position of the error, if it has one.
+fn excerpt(error: &EngineError) -> Vec<(usize, String)> {
+    let Some(e) = error.script_error().filter(|e| e.has_position())
+    else {
+        return vec![];
+    };
+    let Ok(source) = fs::read_to_string(&e.path) else {
+        return vec![];
+    };
+
+    let first = e.line.saturating_sub(EXCERPT_CONTEXT).max(1);
+    let last = e.line + EXCERPT_CONTEXT;
+
+    source
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, untabify(line)))
+        .filter(|(n, _)| (first..=last).contains(n))
+        .collect()
+}
+
+/// The text copied to the clipboard for an error.
+fn report(source: &Path, error: &EngineError) -> String {
+    match error.script_error() {
+        Some(e) => format!("{} in {e:#}", error.kind()),
+        None => format!("{} in {}: {}", error.kind(), source.display(), error.message()),
+    }
+}
+
 impl ErrorPage {
-    pub fn new(errors: Vec<(PathBuf, anyhow::Error)>, ctx: impl ToString) -> Self {
-        Self {
+    pub fn new(errors: ErrorMap, ctx: impl ToString) -> Self {
+        let mut page = Self {
             errors,
             context: ctx.to_string(),
+            excerpts: vec![],
+            scroll: 0.0,
+            runtime: false,
+        };
+        page.read_excerpts();
+        page
+    }
+
+    /// Error page for a running script, offering to retry, reload or go back to the menu.
+    pub fn runtime(errors: ErrorMap, ctx: impl ToString) -> Self {
+        Self {
+            runtime: true,
+            ..Self::new(errors, ctx)
         }
     }
 
-    fn draw_errors(&mut self, y: &mut f32) {
+    pub fn is_runtime(&self) -> bool {
+        self.runtime
+    }
+
+    fn read_excerpts(&mut self) {
+        self.excerpts = self.errors.iter().map(|(_, e)| excerpt(e)).collect();
+    }
+
+    fn draw_errors(&self, ui: &UI, console: &mut Console, y: &mut f32) {
         let screen_w = screen_width();
+        let x = 10.0;
 
-        let size = 30.0;
         for (i, (source, error)) in self.errors.iter().enumerate() {
-            let source = source.to_string_lossy();
+            let script_error = error.script_error();
             let i = i + 1;
 
-            let size = 60.0;
+            let size = 40.0;
             *y += size;
-            draw_centered(&format!("Error #{i}"), *y, size, WHITE);
+            draw_centered(&format!("Error #{i} ({})", error.kind()), *y, size, WHITE);
 
-            // Draw message
+            // Draw location
+            let location = match script_error {
+                Some(e) if e.has_position() => {
+                    format!("{}:{}:{}", e.path.display(), e.line, e.column)
+                }
+                _ => source.to_string_lossy().into_owned(),
+            };
             let size = 30.0;
             *y += size;
-            draw_centered(&format!("Source: {source}"), *y, size, LIGHTGRAY);
-
-            *y += 25.0;
+            draw_centered(&format!("Source: {location}"), *y, size, LIGHTGRAY);
 
             // Draw error
-            let width_base = screen_w;
-            let size = vec2(width_base - 20.0 * 2., 30.0);
+            let message = error.message();
+            *y += size;
+            let rows = draw_clamped(&message, x, *y, size, WHITE);
+            *y += size * rows as f32;
+
+            // Draw the source, with a caret under the error
+            let size = 24.0;
+            for (n, line) in &self.excerpts[i - 1] {
+                let prefix = format!("{n:>4} | ");
+                let is_error_line = script_error.is_some_and(|e| e.line == *n);
+                let color = if is_error_line { WHITE } else { LIGHTGRAY };
 
-            let error = error.to_string();
-            draw_clamped(&error, 10., *y, size.y, WHITE);
+                *y += size;
+                draw_text(&format!("{prefix}{line}"), x, *y, size, color);
+
+                if let Some(e) = script_error.filter(|_| is_error_line) {
+                    let before: String = line.chars().take(e.column.saturating_sub(1)).collect();
+                    let offset = measure_text(&(prefix + before.as_str()), None, size as u16, 1.0).width;
+
+                    *y += size * 0.6;
+                    draw_text("^", x + offset, *y, size, YELLOW);
+                }
+            }
+
+            // Draw the call stack
+            if let Some(e) = script_error {
+                for frame in e.call_stack.iter().rev() {
+                    *y += size;
+                    draw_text(
+                        &format!("in `{}` at {}:{}", frame.function, frame.line, frame.column),
+                        x,
+                        *y,
+                        size,
+                        LIGHTGRAY,
+                    );
+                }
+            }
+
+            // Draw actions
+            *y += 10.0;
+            let mut bounds = Rect::new(x, *y, 120.0, 40.0);
+            if ui.button("Copy", bounds, 20.0) {
+                clipboard_set(&report(source, error));
+                console.log(format!("Copied error #{i} to clipboard"));
+            }
 
-            *y += size.y;
+            let path = script_error.map_or(source.as_path(), |e| e.path.as_path());
+            if path.is_file() {
+                bounds.x += bounds.w + 10.0;
+                bounds.w = 200.0;
+                if ui.button("Open in editor", bounds, 20.0) {
+                    if let Err(e) = cross::open_in_editor(path) {
+                        console.err(format!("Failed to open {path:?}: {e}"));
+                    }
+                }
+            }
+            *y += bounds.h;
+
+            // Separator
+            *y += 10.0;
+            draw_line(x, *y, screen_w - x, *y, 1.0, LIGHTGRAY);
         }
     }
 
-    /// Returns if should keep showing
-    pub fn show(&mut self, engine: &mut impl ScriptEngine, console: &mut Console) -> bool {
+    fn update_scroll(&mut self, content_height: f32) {
+        let page = screen_height() - FOOTER_HEIGHT;
+        let wheel = mouse_wheel().1;
+
+        if wheel != 0.0 {
+            self.scroll -= wheel.signum() * SCROLL_STEP;
+        }
+        if is_key_down(KeyCode::Down) {
+            self.scroll += SCROLL_SPEED * get_frame_time();
+        }
+        if is_key_down(KeyCode::Up) {
+            self.scroll -= SCROLL_SPEED * get_frame_time();
+        }
+        if is_key_pressed(KeyCode::PageDown) {
+            self.scroll += page;
+        }
+        if is_key_pressed(KeyCode::PageUp) {
+            self.scroll -= page;
+        }
+
+        let max_scroll = (content_height - page).max(0.0);
+        self.scroll = self.scroll.clamp(0.0, max_scroll);
+    }
+
+    fn draw_runtime_footer(&self, ui: &UI) -> Option<ErrorAction> {
+        let (sw, sh) = (screen_width(), screen_height());
+        let (w, h, spacing) = (220.0, 40.0, 10.0);
+
+        let mut bounds = Rect::new(
+            (sw - w * 3.0 - spacing * 2.0) / 2.0,
+            sh - FOOTER_HEIGHT + (FOOTER_HEIGHT - h) / 2.0,
+            w,
+            h,
+        );
+        let mut action = None;
+
+        for (text, key, a) in [
+            ("Retry (Enter)", KeyCode::Enter, ErrorAction::Retry),
+            ("Reload (F5)", KeyCode::F5, ErrorAction::Reload),
+            ("Back to menu (Esc)", KeyCode::Escape, ErrorAction::BackToMenu),
+        ] {
+            if ui.button(text, bounds, 20.0) || is_key_pressed(key) {
+                action = Some(a);
+            }
+            bounds.x += w + spacing;
+        }
+
+        action
+    }
+
+    /// Returns `None` while the page should keep showing
+    pub fn show(&mut self, console: &mut Console, ui: &UI) -> Option<ErrorAction> {
         let bg = Color::new(0.7, 0., 0., 1.);
         clear_background(bg);
 
         // Draw Title
-        let (sw, _sh) = (screen_width(), screen_height());
+        let (sw, sh) = (screen_width(), screen_height());
 
         let err_size = (sw / 5.).clamp(10., 200.);
-        draw_centered("ERROR", err_size, err_size, WHITE);
+        let mut y = err_size - self.scroll;
+        draw_centered("ERROR", y, err_size, WHITE);
 
-        let mut y = err_size + 10.0;
-        // Draw error count
+        y += 10.0;
+        // Draw error count and context
         let size = 20.0;
         y += size;
         draw_centered(
-            &format!("Encountered {} errors", self.errors.len()),
+            &format!("Encountered {} errors: {}", self.errors.len(), self.context),
             y,
             size,
             WHITE,
         );
 
-        self.draw_errors(&mut y);
+        self.draw_errors(ui, console, &mut y);
 
-        let size = 50.0;
-        y += size * 2.0;
-        draw_centered("Press Escape to return", y, size, WHITE);
-        let size = 50.0;
-        y += size;
-        draw_centered("Press F5 to reload scripts", y, size, WHITE);
+        self.update_scroll(y + self.scroll);
+
+        // Draw footer
+        draw_rectangle(0.0, sh - FOOTER_HEIGHT, sw, FOOTER_HEIGHT, bg);
+
+        if self.runtime {
+            return self.draw_runtime_footer(ui);
+        }
+
+        let size = 25.0;
+        draw_centered(
+            "Escape: return   F5: reload scripts   Arrows/Wheel: scroll",
+            sh - FOOTER_HEIGHT / 2.0 + size / 3.0,
+            size,
+            WHITE,
+        );
 
         if is_key_pressed(KeyCode::Escape) {
-            return false;
+            return Some(ErrorAction::Close);
         }
         if is_key_pressed(KeyCode::F5) {
-            self.errors.clear();
-            if let Err(e) = engine.load_scripts(
-                console,
-                &mut self.errors,
-                &[ScriptDir::Examples, ScriptDir::Scripts],
-            ) {
-                self.context = e.to_string();
-            }
+            return Some(ErrorAction::Refresh);
         }
 
-        true
+        None
     }
 }
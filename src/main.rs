@@ -3,11 +3,12 @@
 #![cfg_attr(debug_assertions, allow(unused_imports))]
 
 use engine::create_readme;
-use engine::dirs;
 use engine::ENGINE_NAME;
+use clap::Parser;
+use cli::{Cli, Command};
 use menu::Console;
-use std::env;
 use std::process;
+use std::process::ExitCode;
 
 #[allow(unused_imports)]
 use cross::cmd;
@@ -23,16 +24,16 @@ use engine::ScriptDir;
 use engine::ScriptEngine;
 use engine::*;
 
+mod cli;
 mod config;
 mod cross;
 mod error;
-mod ffi;
 mod menu;
-mod texture;
 mod ui;
 
 const PKG_NAME: &str = env!("CARGO_PKG_NAME");
 const PKG_VERSION: &str = env!("CARGO_PKG_VERSION");
+const CONFIG_FILE: &str = "config.toml";
 
 pub mod key {
     use super::*;
@@ -53,13 +54,14 @@ fn window_icon() -> Icon {
     }
 }
 
-fn window() -> Conf {
+fn window(fullscreen: bool, size: Option<(i32, i32)>) -> Conf {
+    let (window_width, window_height) = size.unwrap_or((800, 600));
     Conf {
         window_title: "KGames".to_owned(),
-        window_width: 800,
-        window_height: 600,
+        window_width,
+        window_height,
         high_dpi: true,
-        fullscreen: false,
+        fullscreen,
         sample_count: 4,
         window_resizable: true,
         // Window icon
@@ -72,37 +74,37 @@ fn window() -> Conf {
     }
 }
 
-#[macroquad::main(window)]
-async fn main() {
-    let mut console = Console::new();
-    let dirs = dirs();
-
-    // Check CLI args
-    let args: Vec<_> = env::args().collect();
-    let program = &args[0];
-    let mut preselected_script = None;
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let ctx = match cli.data_dir {
+        Some(ref data_dir) => Context::new(data_dir),
+        None => Context::default(),
+    };
+
+    let (preselected_script, fullscreen, size) = match cli.command {
+        None => (None, false, None),
+        Some(Command::Run {
+            script,
+            fullscreen,
+            size,
+        }) => (Some(script), fullscreen, size),
+        Some(Command::List) => return cli::list(ctx),
+        Some(Command::Check { json }) => return cli::check(ctx, json),
+        Some(Command::New { name, lang }) => return cli::new(ctx.dirs(), &name, lang.as_deref()),
+        Some(Command::Api { json }) => return cli::api(ctx, json),
+    };
+
+    macroquad::Window::from_config(window(fullscreen, size), run(ctx, preselected_script));
+    ExitCode::SUCCESS
+}
 
-    let usage = format!("USAGE: {program} [--help] [--version] <script>");
+async fn run(ctx: Context, preselected_script: Option<String>) {
+    let mut console = Console::new();
+    let dirs = ctx.dirs().clone();
 
-    match args.len() {
-        ..1 => {}
-        2 => match args[1].as_str() {
-            "--help" => {
-                console.print(usage);
-                process::exit(0);
-            }
-            "--version" => {
-                console.print(format!("{PKG_NAME} version {PKG_VERSION}"));
-                process::exit(0);
-            }
-            script => {
-                console.print(format!("starting with script '{script}'"));
-                preselected_script = Some(script);
-            }
-        },
-        too_many => {
-            eprintln!("ERROR: too many args, expected 1, got {too_many}")
-        }
+    if let Some(ref script) = preselected_script {
+        console.print(format!("starting with script '{script}'"));
     }
 
     console.print(format!("{PKG_NAME} version {PKG_VERSION}",));
@@ -111,13 +113,10 @@ async fn main() {
     console.print(format!("Scripting engine: {}", ENGINE_NAME));
     console.print("=========================");
 
-    let mut engine = Engine::new();
+    let mut engine = Engine::new(ctx);
 
     console.log("Scripting engine initialized");
 
-    engine.expose_layer();
-    console.log("Prepared scripting interface");
-
     // Create dirs (if not exist)
     dirs.create().unwrap_or_else(|e| {
         console.err(format!("Failed to create required directories: {e}"));
@@ -128,13 +127,47 @@ async fn main() {
         dirs.root, dirs.scripts, dirs.assets
     ));
 
+    // Load plugins, their errors are shown with the ones of the scripts
+    let mut errors = vec![];
+    let mut api = HostApi::new();
+    for plugin in plugins::load_plugins(&dirs, &mut api, &mut errors) {
+        console.log(format!("Loaded plugin {plugin:?}"));
+    }
+    for (path, e) in &errors {
+        console.err(format!("{path:?}: {}", e.message()));
+    }
+    let plugins_failed = !errors.is_empty();
+
+    engine.expose_api(&api);
+    console.log("Prepared scripting interface");
+
+    // Read config
+    let config_path = dirs.root.join(CONFIG_FILE);
+    let mut limits = Limits::default();
+    if config_path.is_file() {
+        match ::config::Config::read(&config_path) {
+            Ok(config) => {
+                console.log(format!("Read config from {config_path:?}"));
+                limits = config.limits;
+            }
+            Err(e) => console.err(format!("Failed to read config {config_path:?}: {e}")),
+        }
+    }
+    engine.set_limits(&limits);
+
     // Create readme
     let readme = "README.txt";
-    match create_readme(readme) {
+    match create_readme(&dirs, readme) {
         Ok(created) => console.log(&format!("Created readme '{readme}' at {created:?}")),
         Err(e) => console.err(format!("Failed to create readme '{readme}': {e}")),
     }
 
+    // Write API reference
+    match docs::write_api_reference(&engine) {
+        Ok(written) => console.log(format!("Wrote API reference to {written:?}")),
+        Err(e) => console.err(format!("Failed to write API reference: {e}")),
+    }
+
     // Write examples
     let mut warnings = vec![];
     console.log("Writing examples...");
@@ -152,7 +185,6 @@ async fn main() {
 
     // Try to load scripts on startup.
     let mut start_error = None;
-    let mut errors = vec![];
 
     if let Err(e) = engine.load_scripts(
         &mut console,
@@ -163,6 +195,8 @@ async fn main() {
             errors,
             format!("Failed to init scripts: {e}"),
         ));
+    } else if plugins_failed {
+        start_error = Some(ErrorPage::new(errors, "Failed to load plugins"));
     }
 
     // Report script count
@@ -170,7 +204,7 @@ async fn main() {
     if scripts_count == 0 {
         console.log(format!(
             "WARNING: No scripts ending in {} found in {:?}!",
-            Engine::extension(),
+            Engine::extensions().join(" or "),
             dirs.scripts,
         ));
     } else {
@@ -189,18 +223,15 @@ async fn main() {
     ));
     println!();
 
-    // Watch for script changes
-    let mut starting_state = menu::State::Menu;
-
     // Preselected Script
+    let mut preselected_id = None;
     if let Some(preselected) = preselected_script {
         match engine
             .scripts()
             .iter()
-            .enumerate()
-            .find(|(i, s)| s.name() == Some(preselected))
+            .find(|s| s.name() == Some(preselected.as_str()))
         {
-            Some((idx, _script)) => starting_state = menu::State::Playing(idx),
+            Some(script) => preselected_id = Some(script.id().clone()),
             None => {
                 console.err(format!("script '{preselected}' not found."));
             }
@@ -209,7 +240,10 @@ async fn main() {
 
     let mut menu = Menu::new(engine, console, readme);
     menu.error = start_error;
-    menu.state = starting_state;
+    menu.release_idle_scripts();
+    if let Some(id) = preselected_id {
+        menu.play(id);
+    }
 
     loop {
         menu.update();
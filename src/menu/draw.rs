@@ -7,6 +7,58 @@ impl<'a, E: ScriptEngine> Menu<'a, E> {
     pub fn console(&mut self) {
         // NOTE: on MacOS, the key to open it is set to ';' instead of '`'
         self.console.update(&mut self.cvars);
+
+        if let Some(command) = self.console.take_command() {
+            match command.split_whitespace().next() {
+                Some(ASSETS_COMMAND) => self.list_assets(),
+                _ => self.console.err(format!("Unknown command {command:?}")),
+            }
+        }
+    }
+
+    /// Progress of the assets preloaded by a game before it starts.
+    fn draw_loading(&mut self, game: &ScriptId) {
+        clear_background(self.background);
+
+        let name = match self.engine.script(game) {
+            Ok(script) => script.name().unwrap_or("INVALID_NAME").to_owned(),
+            Err(_) => game.to_string(),
+        };
+        let progress = self.engine.context().assets().loading_progress();
+
+        let (screen_w, screen_h) = (screen_width(), screen_height());
+        let (w, h) = ((screen_w / 2.0).clamp(200.0, 600.0), 24.0);
+        let (x, y) = ((screen_w - w) / 2.0, screen_h / 2.0);
+
+        draw_text(&format!("Loading {name}..."), x, y - h, 30.0, self.ui.fg);
+        draw_rectangle(x, y, w * progress, h, self.ui.fg);
+        draw_rectangle_lines(x, y, w, h, 2.0, self.ui.fg);
+    }
+
+    /// Print the loaded assets with their size and the scripts using them.
+    fn list_assets(&mut self) {
+        let assets = self.engine.context().assets();
+        let list = assets.list();
+        if list.is_empty() {
+            self.console.log("No assets loaded");
+            return;
+        }
+
+        for asset in &list {
+            let owners: Vec<_> = asset.owners.iter().map(ToString::to_string).collect();
+            self.console.print(format!(
+                "{:>10}  {:<7}  {}  ({})",
+                human_size(asset.size),
+                asset.kind,
+                asset.path.display(),
+                owners.join(", ")
+            ));
+        }
+        self.console.log(format!(
+            "{} assets loaded, {} in total",
+            list.len(),
+            human_size(assets.total_size())
+        ));
     }
 }
 
@@ -66,8 +118,9 @@ impl<'a, E: ScriptEngine> Menu<'a, E> {
     #[inline]
     pub fn draw(&mut self) {
         if let Some(ref mut err) = self.error {
-            if !err.show(&mut self.engine, &mut self.console) {
+            if let Some(action) = err.show(&mut self.console, &self.ui) {
                 self.error = None;
+                self.on_error_action(action);
             }
             return;
         }
@@ -75,16 +128,18 @@ impl<'a, E: ScriptEngine> Menu<'a, E> {
         let (screen_w, screen_h) = (screen_width(), screen_height());
 
         match self.state {
-            State::Playing(game) => {
-                let script = &mut self.engine.scripts()[game];
-
+            State::Playing(ref game) => {
                 if let Err(e) = self.engine.call_function(game, "draw") {
-                    self.console
-                        .err(format!("Error while executings script: {e}"));
+                    let game = game.clone();
+                    self.halt(&game, "draw", e);
                 }
 
                 return;
             }
+            State::Loading(ref game) => {
+                let game = game.clone();
+                self.draw_loading(&game);
+            }
             State::Menu => {
                 self.draw_menu();
             }
@@ -157,9 +212,9 @@ impl<'a, E: ScriptEngine> Menu<'a, E> {
 
         if self
             .ui
-            .button_icon(self.folder_icon, bounds, "Open engine folder")
+            .button_icon(&self.folder_icon, bounds, "Open engine folder")
         {
-            if let Err(e) = cross::open_path(&dirs().root) {
+            if let Err(e) = cross::open_path(&self.engine.context().dirs().root) {
                 self.console.err(e);
             }
         }
@@ -167,7 +222,7 @@ impl<'a, E: ScriptEngine> Menu<'a, E> {
 
         if self
             .ui
-            .button_icon(self.refresh, bounds, "Refresh and reload all the scripts")
+            .button_icon(&self.refresh, bounds, "Refresh and reload all the scripts")
         {
             self.reload_scripts();
         }
@@ -175,7 +230,7 @@ impl<'a, E: ScriptEngine> Menu<'a, E> {
 
         if self
             .ui
-            .button_icon(self.help, bounds, "Open the README.txt file")
+            .button_icon(&self.help, bounds, "Open the README.txt file")
         {
             if let Err(e) = cross::open_path(&self.readme) {
                 self.console.err(e);
@@ -1,26 +1,119 @@
 use super::Menu;
 use super::*;
+use engine::LogLevel;
 use macroquad::prelude::*;
 use macroquad::ui::{hash, root_ui};
 
+/// Keys starting the first nine games, the game numbered `n` is started by `NUMBER_KEYS[n]`.
+const NUMBER_KEYS: [KeyCode; 10] = [
+    KeyCode::Key0,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
+
 impl<'a, E: ScriptEngine> Menu<'a, E> {
     pub fn draw_games(&mut self) {
         clear_background(self.background);
 
+        if let Some(game) = self.log_panel.clone() {
+            self.draw_log(&game);
+            return;
+        }
+
+        // Sections by category, top level games first
+        let mut games: Vec<_> = self
+            .engine
+            .scripts()
+            .iter()
+            .map(|s| {
+                let name = s.name().unwrap_or("INVALID_NAME").to_owned();
+                (s.category().to_owned(), name, s.id().clone())
+            })
+            .collect();
+        games.sort();
+
+        if games.is_empty() {
+            self.selected_game = 0;
+        } else {
+            self.selected_game = self.selected_game.min(games.len() - 1);
+        }
+        if self.ui.active {
+            if is_key_pressed(KeyCode::Down) && self.selected_game + 1 < games.len() {
+                self.selected_game += 1;
+            }
+            if is_key_pressed(KeyCode::Up) {
+                self.selected_game = self.selected_game.saturating_sub(1);
+            }
+            if is_key_pressed(KeyCode::Enter) {
+                if let Some((_, _, id)) = games.get(self.selected_game) {
+                    self.play(id.clone());
+                    return;
+                }
+            }
+        }
+
         // FIXME: remove
-        for (i, name) in self.engine.scripts().iter().enumerate() {
-            let i = i + 1;
-            let (x, y, w, h) = (200., 200., 500., 50.);
-            draw_rectangle(x, y + h * i as f32, w, h, BLACK);
-            draw_text(
-                &format!("{i} - {}", name.name().unwrap_or("INVALID_NAME")),
-                x,
-                y + h * i as f32 + h / 2.,
-                h * 0.8,
-                WHITE,
-            );
-            if is_key_pressed(unsafe { std::mem::transmute(KeyCode::Key0 as u16 + i as u16) }) {
-                self.state = State::Playing(i - 1);
+        let (x, w, h) = (200., 500., 50.);
+        let top = 200.;
+
+        // Rows of the sections and the games, scrolled so the selected game is visible
+        let mut rows = vec![];
+        let mut y = top;
+        let mut section = "";
+        for (category, _, _) in &games {
+            if category != section {
+                section = category;
+                y += h;
+            }
+            y += h;
+            rows.push(y);
+        }
+        let scroll = rows
+            .get(self.selected_game)
+            .map_or(0., |y| (y + 2. * h - screen_height()).max(0.));
+
+        let mouse: Vec2 = mouse_position().into();
+        let mut section = "";
+        for (i, ((category, name, id), y)) in games.iter().zip(rows).enumerate() {
+            let y = y - scroll;
+
+            if category != section {
+                section = category;
+                draw_text(category, x, y - h / 2., h * 0.8, GRAY);
+            }
+
+            let row = Rect::new(x, y, w, h);
+            let hovered = self.ui.active && row.contains(mouse);
+            if hovered && mouse_delta_position() != Vec2::ZERO {
+                self.selected_game = i;
+            }
+            let bg = if i == self.selected_game { DARKGRAY } else { BLACK };
+            draw_rectangle(x, y, w, h, bg);
+
+            // The first games can also be started with the number keys
+            let number = NUMBER_KEYS.get(i + 1);
+            let label = match number {
+                Some(_) => format!("{} - {name}", i + 1),
+                None => name.clone(),
+            };
+            draw_text(&label, x, y + h / 2., h * 0.8, WHITE);
+
+            let has_log = self.engine.context().has_script_log(id);
+            if has_log && self.ui.button("Log", Rect::new(x + w + 10., y, 80., h), 20.) {
+                self.log_panel = Some(id.clone());
+                return;
+            }
+            let clicked = hovered && is_mouse_button_pressed(MouseButton::Left);
+            let number_pressed = self.ui.active && number.is_some_and(|key| is_key_pressed(*key));
+            if clicked || number_pressed {
+                self.play(id.clone());
                 return;
             }
         }
@@ -33,4 +126,35 @@ impl<'a, E: ScriptEngine> Menu<'a, E> {
         // root_ui().button(vec2(400.0, 200.0), "Hello");
         root_ui().input_text(hash!(), "Search ...", &mut self.ui.query);
     }
+
+    /// What a game logged during its last session, the latest lines at the bottom.
+    fn draw_log(&mut self, game: &ScriptId) {
+        let name = match self.engine.script(game) {
+            Ok(script) => script.name().unwrap_or("INVALID_NAME").to_owned(),
+            Err(_) => game.to_string(),
+        };
+        let (screen_w, screen_h) = (screen_width(), screen_height());
+        let (x, size) = (10.0, 20.0);
+
+        draw_text(&format!("Log of {name}"), x, 40.0, 30.0, self.ui.fg);
+        if self.ui.button("Back", Rect::new(screen_w - 130.0, 10.0, 120.0, 40.0), 20.0) {
+            self.log_panel = None;
+            return;
+        }
+
+        let lines = self.engine.context().script_log_lines(game);
+        let top = 80.0;
+        let visible = ((screen_h - top) / size).max(0.0) as usize;
+        let mut y = top;
+        for line in lines.iter().skip(lines.len().saturating_sub(visible)) {
+            let color = match line.level {
+                LogLevel::Debug => GRAY,
+                LogLevel::Info => WHITE,
+                LogLevel::Warn => YELLOW,
+                LogLevel::Error => RED,
+            };
+            y += size;
+            draw_text(&format!("[{}] {}", line.level, line.text), x, y, size, color);
+        }
+    }
 }
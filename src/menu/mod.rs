@@ -4,14 +4,16 @@ mod settings;
 mod update;
 
 use crate::error::ErrorPage;
-use crate::texture::*;
+use game_core::assets::human_size;
 use crate::ui::rgb;
 use crate::ui::Logger;
 use crate::ui::{Dialog, UI};
 pub use console::*;
-use engine::dirs;
+use engine::watch::ScriptWatcher;
 use engine::GameScript;
+use engine::ScriptDir;
 use engine::ScriptEngine;
+use game_core::ScriptId;
 use macroquad::prelude::*;
 use miniquad::window::dropped_file_bytes;
 use miniquad::window::dropped_file_count;
@@ -20,6 +22,9 @@ use std::path::Path;
 use std::path::PathBuf;
 
 use fuzzy_matcher::skim::SkimMatcherV2;
+
+/// Console command listing the assets loaded by scripts
+const ASSETS_COMMAND: &str = "assets";
 // use fuzzy_matcher::FuzzyMatcher;
 
 #[allow(unused)]
@@ -48,7 +53,19 @@ pub enum State {
     Menu,
     Settings,
     Games,
-    Playing(usize),
+    /// Waiting for the preloaded assets of a game before starting it
+    Loading(ScriptId),
+    Playing(ScriptId),
+}
+
+impl State {
+    /// The game being loaded or played.
+    pub fn game(&self) -> Option<&ScriptId> {
+        match self {
+            Self::Loading(game) | Self::Playing(game) => Some(game),
+            _ => None,
+        }
+    }
 }
 
 pub struct Menu<'a, E: ScriptEngine> {
@@ -61,17 +78,22 @@ pub struct Menu<'a, E: ScriptEngine> {
 
     // Icons
     background: Color,
-    folder_icon: &'a Texture2D,
-    refresh: &'a Texture2D,
-    help: &'a Texture2D,
+    folder_icon: Texture2D,
+    refresh: Texture2D,
+    help: Texture2D,
 
     // Files
     readme: PathBuf,
+    watcher: ScriptWatcher,
 
     // State
     pub state: State,
     dialog: Option<Dialog<'a>>,
     key_entered: bool,
+    /// Game whose log of the last session is shown in the games browser
+    log_panel: Option<ScriptId>,
+    /// Highlighted game of the games browser, moved with the arrow keys and started with Enter
+    selected_game: usize,
 
     // Fzf
     matcher: SkimMatcherV2,
@@ -82,8 +104,10 @@ pub struct Menu<'a, E: ScriptEngine> {
 }
 
 impl<'a, E: ScriptEngine> Menu<'a, E> {
-    pub fn new(engine: E, console: Console, readme_name: impl AsRef<Path>) -> Self {
-        let dirs = dirs();
+    pub fn new(engine: E, mut console: Console, readme_name: impl AsRef<Path>) -> Self {
+        let dirs = engine.context().dirs().clone();
+        console.add_command(ASSETS_COMMAND);
+
         Self {
             show_fps: false,
             error: None,
@@ -94,16 +118,23 @@ impl<'a, E: ScriptEngine> Menu<'a, E> {
 
             // Icons
             background: rgb(0.11, 0.12, 0.12),
-            folder_icon: asset_store().get_texture("folder_open_file").unwrap(),
-            refresh: asset_store().get_texture("search_file").unwrap(),
-            help: asset_store().get_texture("help_book").unwrap(),
+            folder_icon: engine.context().builtin_texture("folder_open_file").unwrap(),
+            refresh: engine.context().builtin_texture("search_file").unwrap(),
+            help: engine.context().builtin_texture("help_book").unwrap(),
             // Files
             readme: dirs.root.join(readme_name),
+            watcher: ScriptWatcher::new::<E>(&dirs, &[
+                ScriptDir::Scripts,
+                ScriptDir::Examples,
+                ScriptDir::Lib,
+            ]),
 
             // State
             state: State::Menu,
             dialog: None,
             key_entered: false,
+            log_panel: None,
+            selected_game: 0,
 
             // Fzf
             matcher: SkimMatcherV2::default(),
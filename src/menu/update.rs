@@ -1,27 +1,199 @@
 use super::*;
 use crate::key;
-use crate::{cross::fuzzy_search, error::ErrorPage};
-use engine::GameScript;
-use engine::ScriptDir;
+use crate::cross::fuzzy_search;
+use crate::error::{ErrorAction, ErrorPage};
+use engine::{ErrorMap, GameScript};
+use game_core::ScriptId;
 use engine::ScriptEngine;
 use macroquad::prelude::*;
+use std::mem;
+use std::path::PathBuf;
+use std::time::Duration;
 use miniquad::window::{dropped_file_bytes, dropped_file_count, dropped_file_path};
 
+/// Called once the preloaded assets are loaded, if the script defines it
+const INIT_HOOK: &str = "init";
+/// Time spent loading preloaded assets each frame
+const LOAD_BUDGET: Duration = Duration::from_millis(8);
+
 impl<'a, E: ScriptEngine> Menu<'a, E> {
     pub fn reload_scripts(&mut self) {
         self.console.log("### Reloading scripts");
+
         let mut errors = vec![];
-        if let Err(e) = self.engine.load_scripts(
-            &mut self.console,
-            &mut errors,
-            &[ScriptDir::Examples, ScriptDir::Scripts],
-        ) {
+        let result = self.engine.reload_scripts(&mut self.console, &mut errors);
+        self.after_reload(result, errors);
+    }
+
+    /// Reload the scripts whose files the watcher saw change, the others keep their state.
+    fn reload_changed(&mut self, changed: &[PathBuf]) {
+        self.console.log(format!("Detected changes in {changed:?}"));
+
+        let mut errors = vec![];
+        let result = self.engine.reload_changed(&mut self.console, &mut errors, changed);
+        self.after_reload(result, errors);
+    }
+
+    fn after_reload(&mut self, result: anyhow::Result<()>, errors: ErrorMap) {
+        if let Err(e) = result {
             self.error = Some(ErrorPage::new(errors, e));
+        } else if self.error.as_ref().is_some_and(|e| !e.is_runtime()) {
+            // The errors were fixed
+            self.error = None;
+        }
+
+        // Search results are indices into the scripts
+        self.matches.clear();
+        if let Some(game) = self.state.game() {
+            self.follow_playing(game.clone());
         }
+        self.release_idle_scripts();
     }
+
+    /// Stop the scripts the last load ran but the playing one, their top level code may have loaded assets.
+    /// The other scripts were already stopped.
+    pub fn release_idle_scripts(&mut self) {
+        let playing = self.state.game().cloned();
+        let idle: Vec<_> = self
+            .engine
+            .loaded_scripts()
+            .iter()
+            .filter(|id| Some(*id) != playing.as_ref())
+            .cloned()
+            .collect();
+
+        for id in idle {
+            if let Err(e) = self.engine.stop_script(&id) {
+                self.console.err(format!("Failed to stop script {id}: {e}"));
+            }
+        }
+    }
+
+    /// Go back to the menu, the running game is stopped and its assets freed.
+    pub fn back_to_menu(&mut self) {
+        if let Some(game) = mem::replace(&mut self.state, State::Menu).game() {
+            if let Err(e) = self.engine.stop_script(&game) {
+                self.console.err(format!("Failed to stop script {game}: {e}"));
+            }
+        }
+    }
+
+    /// Find the playing script again after a reload, or go back to the menu if it was deleted.
+    fn follow_playing(&mut self, game: ScriptId) {
+        let game = self
+            .engine
+            .renamed_scripts()
+            .iter()
+            .find(|(old, _)| *old == game)
+            .map_or(game, |(_, new)| new.clone());
+
+        match self.engine.script(&game) {
+            Ok(_) => {
+                if let State::Loading(ref mut id) | State::Playing(ref mut id) = self.state {
+                    *id = game;
+                }
+            }
+            Err(_) => {
+                self.console
+                    .err(format!("Script {game} was deleted, returning to the menu"));
+                self.state = State::Menu;
+                if self.error.as_ref().is_some_and(ErrorPage::is_runtime) {
+                    self.error = None;
+                }
+            }
+        }
+    }
+    /// Pause the running script and show the error.
+    pub fn halt(&mut self, game: &ScriptId, hook: &str, e: anyhow::Error) {
+        self.halt_all(hook, vec![(game.path().to_path_buf(), e.into())]);
+    }
+
+    /// Pause the running script and show all the errors.
+    fn halt_all(&mut self, hook: &str, errors: ErrorMap) {
+        for (_, e) in &errors {
+            self.console
+                .err(format!("Error while executing script -> {hook}(): {}", e.message()));
+        }
+
+        self.error = Some(ErrorPage::runtime(
+            errors,
+            format!("{hook}() failed, the script is paused"),
+        ));
+    }
+
+    /// Start a game from the beginning, once the assets it preloads are loaded.
+    pub fn play(&mut self, game: ScriptId) {
+        self.log_panel = None;
+        self.engine.context().clear_script_log(&game);
+        if let Err(e) = self.engine.restart_script(&game) {
+            // The script is half initialized, so it is neither loaded nor started
+            self.halt(&game, "init", e);
+            return;
+        }
+        self.state = State::Loading(game.clone());
+
+        if !self.engine.context().assets().is_loading() {
+            self.start(game);
+        }
+    }
+
+    /// Call the optional `init()` of a loaded game and start playing it.
+    fn start(&mut self, game: ScriptId) {
+        self.state = State::Playing(game.clone());
+        if self.engine.has_function(&game, INIT_HOOK) {
+            if let Err(e) = self.engine.call_function(&game, INIT_HOOK) {
+                self.halt(&game, INIT_HOOK, e);
+            }
+        }
+    }
+
+    /// Load some of the preloaded assets, without blocking the frame for long.
+    fn load_pending(&mut self) {
+        let errors = self.engine.context().assets_mut().load_pending(LOAD_BUDGET);
+        if !errors.is_empty() {
+            let errors = errors.into_iter().map(|e| (e.path.clone(), e.into())).collect();
+            self.halt_all("preload", errors);
+        }
+    }
+
+    pub fn on_error_action(&mut self, action: ErrorAction) {
+        match action {
+            ErrorAction::Close | ErrorAction::Retry => {}
+            ErrorAction::Refresh => {
+                self.reload_scripts();
+            }
+            ErrorAction::Reload => {
+                self.reload_scripts();
+                if let (None, Some(game)) = (&self.error, self.state.game()) {
+                    self.play(game.clone());
+                }
+            }
+            ErrorAction::BackToMenu => {
+                self.back_to_menu();
+            }
+        }
+    }
+
     /// Call update() of the script, and update menu state
     #[inline]
     pub fn update(&mut self) {
+        // Hot reload, the running script keeps its state
+        let changed = self.watcher.poll();
+        if !changed.is_empty() {
+            self.reload_changed(&changed);
+        }
+        // Lines queued by the functions called by scripts
+        self.engine.context().flush_console(&mut self.console);
+
+        let reloaded = self.engine.context().assets_mut().poll_changes();
+        for reloaded in reloaded {
+            let (kind, path) = (reloaded.kind, reloaded.path);
+            match reloaded.result {
+                Ok(()) => self.console.log(format!("Reloaded changed {kind} {path:?}")),
+                Err(e) => self.console.err(format!("Failed to reload {kind} {path:?}: {e}")),
+            }
+        }
+
         let console_open = self.console.is_open();
         self.ui.active = !console_open;
 
@@ -44,7 +216,8 @@ impl<'a, E: ScriptEngine> Menu<'a, E> {
 
                     if !needle.is_empty() {
                         // A Key was entered into the search bar
-                        let haystack = self.engine.scripts().iter().filter_map(|s| s.name());
+                        let scripts = self.engine.scripts();
+                        let haystack = scripts.iter().filter_map(|s| s.name());
 
                         let min_score = 20;
 
@@ -65,10 +238,28 @@ impl<'a, E: ScriptEngine> Menu<'a, E> {
         }
 
         match self.state {
-            State::Playing(game) => {
-                if let Err(e) = self.engine.call_function(game, "update") {
-                    self.console
-                        .err(format!("Error while executing script -> update(): {e}"));
+            State::Loading(ref game) => {
+                let game = game.clone();
+                self.load_pending();
+                if self.error.is_none() && !self.engine.context().assets().is_loading() {
+                    self.start(game);
+                }
+                return;
+            }
+            State::Playing(ref game) => {
+                let game = game.clone();
+
+                // Assets preloaded while playing are loaded in the background
+                if self.engine.context().assets().is_loading() {
+                    self.load_pending();
+                    if self.error.is_some() {
+                        return;
+                    }
+                }
+
+                if let Err(e) = self.engine.call_function(&game, "update") {
+                    self.halt(&game, "update", e);
+                    return;
                 }
             }
             State::Settings => {}
@@ -81,13 +272,10 @@ impl<'a, E: ScriptEngine> Menu<'a, E> {
                 self.dialog = None;
                 return;
             }
-            // Reset game on escape
-            if let State::Playing(game) = self.state {
-                let script = &mut self.engine.scripts()[game];
-                script.reset();
-                self.reload_scripts();
+            if self.log_panel.take().is_some() {
+                return;
             }
-            self.state = State::Menu;
+            self.back_to_menu();
         }
     }
 }